@@ -0,0 +1,113 @@
+/**
+ * Optional attributes of a neighbour link. A link is either just its length (the original
+ * format, e.g. "264") or an object with the length and any of the attributes:
+ *
+ *   {"length": 264, "street": "SW Nevada St", "class": "residential", "surface": "asphalt",
+ *    "bike_lane": true, "oneway": false}
+ *
+ * Attributes can weight the cost of a street, e.g. 'surface=gravel:1.5' makes gravel cost half
+ * as much again as its length.
+ */
+use serde::{Deserialize, Serialize};
+
+use std::str::FromStr;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Link {
+    Length(String),
+    Street {
+        length : f64,
+        #[serde(flatten)]
+        attributes : Attributes
+    }
+}
+
+impl Link {
+    // The length, if it is a number at all.
+    pub fn checked_length(&self) -> Option<f64> {
+        match self {
+            Link::Length(length) => length.trim().parse::<f64>().ok(),
+            Link::Street { length, .. } => Some(*length)
+        }
+    }
+
+    pub fn attributes(&self) -> Attributes {
+        match self {
+            Link::Length(_) => Attributes::default(),
+            Link::Street { attributes, .. } => attributes.clone()
+        }
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Attributes {
+    // Name of the street, e.g. 'SW Nevada St'.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub street : Option<String>,
+    // Road class, e.g. the OpenStreetMap highway tag: 'residential', 'primary', 'cycleway'.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class : Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub surface : Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bike_lane : Option<bool>,
+    // The street may only be ridden from this intersection to the neighbour. A link listed back
+    // the other way is ignored by the directed and mixed modes.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub oneway : bool
+}
+
+impl Attributes {
+    pub fn is_empty(&self) -> bool {
+        *self == Attributes::default()
+    }
+
+    // Value of an attribute as text; flags read 'yes' or 'no'.
+    pub fn get(&self, key : &str) -> Option<String> {
+        let flag = |set : bool| if set { "yes".to_string() } else { "no".to_string() };
+        match key {
+            "street" => self.street.clone(),
+            "class" => self.class.clone(),
+            "surface" => self.surface.clone(),
+            "bike_lane" => self.bike_lane.map(flag),
+            "oneway" => Some(flag(self.oneway)),
+            _ => None
+        }
+    }
+}
+
+// Multiplies the cost of every street whose 'key' attribute is 'value', e.g. 'class=primary:2'.
+#[derive(Clone)]
+pub struct Weight {
+    pub key : String,
+    pub value : String,
+    pub factor : f64
+}
+
+impl FromStr for Weight {
+    type Err = String;
+
+    fn from_str(text : &str) -> Result<Weight, String> {
+        let usage = || format!("expected ATTRIBUTE=VALUE:FACTOR, got '{}'", text);
+        let (key, rest) = text.split_once('=').ok_or_else(usage)?;
+        let (value, factor) = rest.rsplit_once(':').ok_or_else(usage)?;
+        let factor : f64 = factor.parse().map_err(|_| usage())?;
+        if !["street", "class", "surface", "bike_lane", "oneway"].contains(&key) {
+            return Err(format!("unknown attribute '{}'", key));
+        }
+        if !(factor.is_finite() && factor > 0.0) {
+            return Err(format!("factor must be a positive number, got {}", factor));
+        }
+        Ok(Weight { key: key.to_string(), value: value.to_string(), factor })
+    }
+}
+
+// Product of the factors of every weight the attributes match.
+pub fn factor(attributes : & Attributes, weights : & [Weight]) -> f64 {
+    weights
+        .iter()
+        .filter(|weight| attributes.get(&weight.key).as_deref() == Some(weight.value.as_str()))
+        .map(|weight| weight.factor)
+        .product()
+}
@@ -0,0 +1,661 @@
+/**
+ * Minimum weight perfect matching on a general (non-bipartite) graph using Edmonds' blossom
+ * algorithm with the primal-dual weighted extension.
+ * Based of Joris van Rantwijk's O(n^3) implementation: http://jorisvr.nl/article/maximum-matching
+ */
+use log::{debug,info};
+
+use ansi_term::Colour;
+use std::collections::HashMap;
+
+use crate::INFINITE;
+use crate::munkres::Matrix;
+
+const NONE : isize = -1;
+
+struct State {
+    // (i, j, weight) with vertices indexed from 0.
+    edges : Vec<(usize, usize, i64)>,
+    nvertex : usize,
+    // endpoint[p] is the vertex at end 'p' of edge 'p / 2'.
+    endpoint : Vec<usize>,
+    // Remote endpoints of the edges incident to each vertex.
+    neighbend : Vec<Vec<usize>>,
+    // Remote endpoint of the matched edge, or NONE.
+    mate : Vec<isize>,
+    // 0 = free, 1 = S (outer), 2 = T (inner); indexed by vertex and blossom.
+    label : Vec<usize>,
+    labelend : Vec<isize>,
+    inblossom : Vec<usize>,
+    blossomparent : Vec<isize>,
+    blossomchilds : Vec<Vec<usize>>,
+    blossombase : Vec<isize>,
+    blossomendps : Vec<Vec<usize>>,
+    bestedge : Vec<isize>,
+    blossombestedges : Vec<Option<Vec<usize>>>,
+    unusedblossoms : Vec<usize>,
+    dualvar : Vec<i64>,
+    allowedge : Vec<bool>,
+    queue : Vec<usize>
+}
+
+impl State {
+    fn new(edges : Vec<(usize, usize, i64)>, nvertex : usize) -> State {
+        let maxweight = edges.iter().map(|&(_, _, wt)| wt).max().unwrap_or(0).max(0);
+        let endpoint = (0..2 * edges.len())
+            .map(|p| if p % 2 == 0 { edges[p / 2].0 } else { edges[p / 2].1 })
+            .collect();
+        let mut neighbend = vec![Vec::new(); nvertex];
+        for (k, &(i, j, _)) in edges.iter().enumerate() {
+            neighbend[i].push(2 * k + 1);
+            neighbend[j].push(2 * k);
+        }
+        let nedge = edges.len();
+        State {
+            edges,
+            nvertex,
+            endpoint,
+            neighbend,
+            mate: vec![NONE; nvertex],
+            label: vec![0; 2 * nvertex],
+            labelend: vec![NONE; 2 * nvertex],
+            inblossom: (0..nvertex).collect(),
+            blossomparent: vec![NONE; 2 * nvertex],
+            blossomchilds: vec![Vec::new(); 2 * nvertex],
+            blossombase: (0..nvertex as isize).chain(vec![NONE; nvertex]).collect(),
+            blossomendps: vec![Vec::new(); 2 * nvertex],
+            bestedge: vec![NONE; 2 * nvertex],
+            blossombestedges: vec![None; 2 * nvertex],
+            unusedblossoms: (nvertex..2 * nvertex).collect(),
+            dualvar: vec![maxweight; nvertex].into_iter().chain(vec![0; nvertex]).collect(),
+            allowedge: vec![false; nedge],
+            queue: Vec::new()
+        }
+    }
+
+    fn slack(&self, k : usize) -> i64 {
+        let (i, j, wt) = self.edges[k];
+        self.dualvar[i] + self.dualvar[j] - 2 * wt
+    }
+
+    fn leaves(&self, b : usize) -> Vec<usize> {
+        if b < self.nvertex {
+            return vec![b];
+        }
+        let mut leaves = Vec::new();
+        for &t in &self.blossomchilds[b] {
+            if t < self.nvertex {
+                leaves.push(t);
+            } else {
+                leaves.extend(self.leaves(t));
+            }
+        }
+        leaves
+    }
+
+    // Children are walked around the blossom cycle, so negative positions wrap.
+    fn child(&self, b : usize, j : isize) -> usize {
+        let childs = &self.blossomchilds[b];
+        childs[j.rem_euclid(childs.len() as isize) as usize]
+    }
+
+    fn endp(&self, b : usize, j : isize) -> usize {
+        let endps = &self.blossomendps[b];
+        endps[j.rem_euclid(endps.len() as isize) as usize]
+    }
+
+    fn assign_label(&mut self, w : usize, t : usize, p : isize) {
+        let b = self.inblossom[w];
+        self.label[w] = t;
+        self.label[b] = t;
+        self.labelend[w] = p;
+        self.labelend[b] = p;
+        self.bestedge[w] = NONE;
+        self.bestedge[b] = NONE;
+        if t == 1 {
+            // b became an S-vertex/blossom; add its vertices to the queue.
+            let leaves = self.leaves(b);
+            self.queue.extend(leaves);
+        } else if t == 2 {
+            // b became a T-vertex/blossom; label its mate S.
+            let base = self.blossombase[b] as usize;
+            let mate = self.mate[base];
+            self.assign_label(self.endpoint[mate as usize], 1, mate ^ 1);
+        }
+    }
+
+    // Traces back from v and w to find either a new blossom (returning its base) or an
+    // augmenting path (returning NONE).
+    fn scan_blossom(&mut self, mut v : isize, mut w : isize) -> isize {
+        let mut path = Vec::new();
+        let mut base = NONE;
+        while v != NONE || w != NONE {
+            let mut b = self.inblossom[v as usize];
+            if self.label[b] & 4 != 0 {
+                base = self.blossombase[b];
+                break;
+            }
+            path.push(b);
+            self.label[b] = 5;
+            if self.labelend[b] == NONE {
+                // Reached a single vertex, stop tracing this side.
+                v = NONE;
+            } else {
+                v = self.endpoint[self.labelend[b] as usize] as isize;
+                b = self.inblossom[v as usize];
+                v = self.endpoint[self.labelend[b] as usize] as isize;
+            }
+            if w != NONE {
+                std::mem::swap(&mut v, &mut w);
+            }
+        }
+        for b in path {
+            self.label[b] = 1;
+        }
+        base
+    }
+
+    // Builds a new blossom with the given base, through the S-S edge k.
+    fn add_blossom(&mut self, base : usize, k : usize) {
+        let (mut v, mut w, _) = self.edges[k];
+        let bb = self.inblossom[base];
+        let mut bv = self.inblossom[v];
+        let mut bw = self.inblossom[w];
+        let b = self.unusedblossoms.pop().unwrap();
+        debug!("add_blossom {} with base {}.", b, base);
+        self.blossombase[b] = base as isize;
+        self.blossomparent[b] = NONE;
+        self.blossomparent[bb] = b as isize;
+
+        let mut path = Vec::new();
+        let mut endps = Vec::new();
+        while bv != bb {
+            self.blossomparent[bv] = b as isize;
+            path.push(bv);
+            endps.push(self.labelend[bv] as usize);
+            v = self.endpoint[self.labelend[bv] as usize];
+            bv = self.inblossom[v];
+        }
+        path.push(bb);
+        path.reverse();
+        endps.reverse();
+        endps.push(2 * k);
+        while bw != bb {
+            self.blossomparent[bw] = b as isize;
+            path.push(bw);
+            endps.push((self.labelend[bw] ^ 1) as usize);
+            w = self.endpoint[self.labelend[bw] as usize];
+            bw = self.inblossom[w];
+        }
+        self.blossomchilds[b] = path.clone();
+        self.blossomendps[b] = endps;
+
+        self.label[b] = 1;
+        self.labelend[b] = self.labelend[bb];
+        self.dualvar[b] = 0;
+        for v in self.leaves(b) {
+            if self.label[self.inblossom[v]] == 2 {
+                // This T-vertex now turns into an S-vertex.
+                self.queue.push(v);
+            }
+            self.inblossom[v] = b;
+        }
+
+        // Compute the least-slack edge to each neighbouring S-blossom.
+        let mut bestedgeto : Vec<isize> = vec![NONE; 2 * self.nvertex];
+        for bv in path {
+            let nblists : Vec<Vec<usize>> = match self.blossombestedges[bv].take() {
+                Some(list) => vec![list],
+                None => self.leaves(bv)
+                    .into_iter()
+                    .map(|v| self.neighbend[v].iter().map(|p| p / 2).collect())
+                    .collect()
+            };
+            for nblist in nblists {
+                for k in nblist {
+                    let (mut i, mut j, _) = self.edges[k];
+                    if self.inblossom[j] == b {
+                        std::mem::swap(&mut i, &mut j);
+                    }
+                    let bj = self.inblossom[j];
+                    if bj != b && self.label[bj] == 1 &&
+                        (bestedgeto[bj] == NONE || self.slack(k) < self.slack(bestedgeto[bj] as usize)) {
+                        bestedgeto[bj] = k as isize;
+                    }
+                }
+            }
+            self.bestedge[bv] = NONE;
+        }
+        let best : Vec<usize> = bestedgeto.into_iter().filter(|&k| k != NONE).map(|k| k as usize).collect();
+        self.bestedge[b] = NONE;
+        for &k in &best {
+            if self.bestedge[b] == NONE || self.slack(k) < self.slack(self.bestedge[b] as usize) {
+                self.bestedge[b] = k as isize;
+            }
+        }
+        self.blossombestedges[b] = Some(best);
+    }
+
+    // Expands the given top-level blossom.
+    fn expand_blossom(&mut self, b : usize, endstage : bool) {
+        debug!("expand_blossom {}.", b);
+        for s in self.blossomchilds[b].clone() {
+            self.blossomparent[s] = NONE;
+            if s < self.nvertex {
+                self.inblossom[s] = s;
+            } else if endstage && self.dualvar[s] == 0 {
+                self.expand_blossom(s, endstage);
+            } else {
+                for v in self.leaves(s) {
+                    self.inblossom[v] = s;
+                }
+            }
+        }
+
+        // If we expand a T-blossom during a stage, its sub-blossoms must be relabeled.
+        if !endstage && self.label[b] == 2 {
+            let entrychild = self.inblossom[self.endpoint[(self.labelend[b] ^ 1) as usize]];
+            let len = self.blossomchilds[b].len() as isize;
+            let mut j = self.blossomchilds[b].iter().position(|&c| c == entrychild).unwrap() as isize;
+            let (jstep, endptrick) : (isize, isize) = if j & 1 == 1 {
+                j -= len;
+                (1, 0)
+            } else {
+                (-1, 1)
+            };
+            let mut p = self.labelend[b];
+            while j != 0 {
+                self.label[self.endpoint[(p ^ 1) as usize]] = 0;
+                let q = self.endp(b, j - endptrick) as isize;
+                self.label[self.endpoint[(q ^ endptrick ^ 1) as usize]] = 0;
+                self.assign_label(self.endpoint[(p ^ 1) as usize], 2, p);
+                self.allowedge[(q / 2) as usize] = true;
+                j += jstep;
+                p = self.endp(b, j - endptrick) as isize ^ endptrick;
+                self.allowedge[(p / 2) as usize] = true;
+                j += jstep;
+            }
+            let bv = self.child(b, j);
+            let end = self.endpoint[(p ^ 1) as usize];
+            self.label[end] = 2;
+            self.label[bv] = 2;
+            self.labelend[end] = p;
+            self.labelend[bv] = p;
+            self.bestedge[bv] = NONE;
+            j += jstep;
+            while self.child(b, j) != entrychild {
+                let bv = self.child(b, j);
+                if self.label[bv] == 1 {
+                    j += jstep;
+                    continue;
+                }
+                let leaves = self.leaves(bv);
+                let v = *leaves.iter().find(|&&v| self.label[v] != 0).unwrap_or(leaves.last().unwrap());
+                if self.label[v] != 0 {
+                    self.label[v] = 0;
+                    let mate = self.mate[self.blossombase[bv] as usize];
+                    self.label[self.endpoint[mate as usize]] = 0;
+                    self.assign_label(v, 2, self.labelend[v]);
+                }
+                j += jstep;
+            }
+        }
+
+        self.label[b] = 0;
+        self.labelend[b] = NONE;
+        self.blossomchilds[b] = Vec::new();
+        self.blossomendps[b] = Vec::new();
+        self.blossombase[b] = NONE;
+        self.blossombestedges[b] = None;
+        self.bestedge[b] = NONE;
+        self.unusedblossoms.push(b);
+    }
+
+    // Swaps matched/unmatched edges over an alternating path through blossom b between
+    // vertex v and the base vertex.
+    fn augment_blossom(&mut self, b : usize, v : usize) {
+        let mut t = v;
+        while self.blossomparent[t] != b as isize {
+            t = self.blossomparent[t] as usize;
+        }
+        if t >= self.nvertex {
+            self.augment_blossom(t, v);
+        }
+        let i = self.blossomchilds[b].iter().position(|&c| c == t).unwrap();
+        let mut j = i as isize;
+        let (jstep, endptrick) : (isize, isize) = if i & 1 == 1 {
+            j -= self.blossomchilds[b].len() as isize;
+            (1, 0)
+        } else {
+            (-1, 1)
+        };
+        while j != 0 {
+            j += jstep;
+            let t = self.child(b, j);
+            let p = self.endp(b, j - endptrick) ^ endptrick as usize;
+            if t >= self.nvertex {
+                self.augment_blossom(t, self.endpoint[p]);
+            }
+            j += jstep;
+            let t = self.child(b, j);
+            if t >= self.nvertex {
+                self.augment_blossom(t, self.endpoint[p ^ 1]);
+            }
+            self.mate[self.endpoint[p]] = (p ^ 1) as isize;
+            self.mate[self.endpoint[p ^ 1]] = p as isize;
+        }
+        // Rotate the children so the new base is first.
+        self.blossomchilds[b].rotate_left(i);
+        self.blossomendps[b].rotate_left(i);
+        self.blossombase[b] = self.blossombase[self.blossomchilds[b][0]];
+    }
+
+    // Swaps matched/unmatched edges over the augmenting path through edge k.
+    fn augment_matching(&mut self, k : usize) {
+        let (v, w, _) = self.edges[k];
+        for (mut s, mut p) in [(v, 2 * k + 1), (w, 2 * k)] {
+            loop {
+                let bs = self.inblossom[s];
+                if bs >= self.nvertex {
+                    self.augment_blossom(bs, s);
+                }
+                self.mate[s] = p as isize;
+                if self.labelend[bs] == NONE {
+                    // Reached a single vertex.
+                    break;
+                }
+                let t = self.endpoint[self.labelend[bs] as usize];
+                let bt = self.inblossom[t];
+                s = self.endpoint[self.labelend[bt] as usize];
+                let j = self.endpoint[(self.labelend[bt] ^ 1) as usize];
+                if bt >= self.nvertex {
+                    self.augment_blossom(bt, j);
+                }
+                self.mate[j] = self.labelend[bt];
+                p = (self.labelend[bt] ^ 1) as usize;
+            }
+        }
+    }
+
+    // Scans the queued S-vertices; returns true once the matching was augmented.
+    fn scan(&mut self) -> bool {
+        while let Some(v) = self.queue.pop() {
+            for p in self.neighbend[v].clone() {
+                let k = p / 2;
+                let w = self.endpoint[p];
+                if self.inblossom[v] == self.inblossom[w] {
+                    continue;
+                }
+                let mut kslack = 0;
+                if !self.allowedge[k] {
+                    kslack = self.slack(k);
+                    if kslack <= 0 {
+                        self.allowedge[k] = true;
+                    }
+                }
+                if self.allowedge[k] {
+                    if self.label[self.inblossom[w]] == 0 {
+                        self.assign_label(w, 2, (p ^ 1) as isize);
+                    } else if self.label[self.inblossom[w]] == 1 {
+                        let base = self.scan_blossom(v as isize, w as isize);
+                        if base >= 0 {
+                            self.add_blossom(base as usize, k);
+                        } else {
+                            self.augment_matching(k);
+                            return true;
+                        }
+                    } else if self.label[w] == 0 {
+                        self.label[w] = 2;
+                        self.labelend[w] = (p ^ 1) as isize;
+                    }
+                } else if self.label[self.inblossom[w]] == 1 {
+                    let b = self.inblossom[v];
+                    if self.bestedge[b] == NONE || kslack < self.slack(self.bestedge[b] as usize) {
+                        self.bestedge[b] = k as isize;
+                    }
+                } else if self.label[w] == 0 && (self.bestedge[w] == NONE || kslack < self.slack(self.bestedge[w] as usize)) {
+                    self.bestedge[w] = k as isize;
+                }
+            }
+        }
+        false
+    }
+
+    // Updates the dual variables; returns false once no further improvement is possible.
+    fn update_duals(&mut self) -> bool {
+        let n = self.nvertex;
+        // Maximum cardinality, so delta type 1 only applies when nothing else does.
+        let mut deltatype = 0;
+        let mut delta : i64 = 0;
+        let mut deltaedge = 0;
+        let mut deltablossom = 0;
+
+        for v in 0..n {
+            if self.label[self.inblossom[v]] == 0 && self.bestedge[v] != NONE {
+                let d = self.slack(self.bestedge[v] as usize);
+                if deltatype == 0 || d < delta {
+                    delta = d;
+                    deltatype = 2;
+                    deltaedge = self.bestedge[v] as usize;
+                }
+            }
+        }
+        for b in 0..2 * n {
+            if self.blossomparent[b] == NONE && self.label[b] == 1 && self.bestedge[b] != NONE {
+                let d = self.slack(self.bestedge[b] as usize) / 2;
+                if deltatype == 0 || d < delta {
+                    delta = d;
+                    deltatype = 3;
+                    deltaedge = self.bestedge[b] as usize;
+                }
+            }
+        }
+        for b in n..2 * n {
+            if self.blossombase[b] >= 0 && self.blossomparent[b] == NONE && self.label[b] == 2 &&
+                (deltatype == 0 || self.dualvar[b] < delta) {
+                delta = self.dualvar[b];
+                deltatype = 4;
+                deltablossom = b;
+            }
+        }
+        if deltatype == 0 {
+            deltatype = 1;
+            delta = self.dualvar[..n].iter().cloned().min().unwrap_or(0).max(0);
+        }
+
+        for v in 0..n {
+            match self.label[self.inblossom[v]] {
+                1 => self.dualvar[v] -= delta,
+                2 => self.dualvar[v] += delta,
+                _ => ()
+            }
+        }
+        for b in n..2 * n {
+            if self.blossombase[b] >= 0 && self.blossomparent[b] == NONE {
+                match self.label[b] {
+                    1 => self.dualvar[b] += delta,
+                    2 => self.dualvar[b] -= delta,
+                    _ => ()
+                }
+            }
+        }
+
+        match deltatype {
+            2 => {
+                self.allowedge[deltaedge] = true;
+                let (mut i, mut j, _) = self.edges[deltaedge];
+                if self.label[self.inblossom[i]] == 0 {
+                    std::mem::swap(&mut i, &mut j);
+                }
+                self.queue.push(i);
+            },
+            3 => {
+                self.allowedge[deltaedge] = true;
+                let (i, _, _) = self.edges[deltaedge];
+                self.queue.push(i);
+            },
+            4 => self.expand_blossom(deltablossom, false),
+            _ => return false
+        }
+        true
+    }
+
+    // Runs one stage per augmentation; returns the mate of each vertex.
+    fn run(&mut self) -> Vec<isize> {
+        let n = self.nvertex;
+        for _ in 0..n {
+            self.label.iter_mut().for_each(|l| *l = 0);
+            self.bestedge.iter_mut().for_each(|e| *e = NONE);
+            self.blossombestedges[n..].iter_mut().for_each(|e| *e = None);
+            self.allowedge.iter_mut().for_each(|a| *a = false);
+            self.queue.clear();
+
+            for v in 0..n {
+                if self.mate[v] == NONE && self.label[self.inblossom[v]] == 0 {
+                    self.assign_label(v, 1, NONE);
+                }
+            }
+
+            let mut augmented = false;
+            loop {
+                if self.scan() {
+                    augmented = true;
+                    break;
+                }
+                if !self.update_duals() {
+                    break;
+                }
+            }
+            if !augmented {
+                break;
+            }
+
+            // End of stage; expand all S-blossoms with zero dual.
+            for b in n..2 * n {
+                if self.blossomparent[b] == NONE && self.blossombase[b] >= 0 &&
+                    self.label[b] == 1 && self.dualvar[b] == 0 {
+                    self.expand_blossom(b, true);
+                }
+            }
+        }
+
+        (0..n)
+            .map(|v| if self.mate[v] >= 0 { self.endpoint[self.mate[v] as usize] as isize } else { NONE })
+            .collect()
+    }
+}
+
+// Pairs up the odd nodes (positions 1.. in 'array') at minimum total distance. Returns the
+// (smaller, larger) pairs, sorted by row.
+pub fn solve(array : & Matrix<i64>, keys : & HashMap<usize, (String, usize)>) -> Vec<(usize, usize)> {
+    let size = array.len();
+    let n = size.saturating_sub(1);
+
+    let mut edges : Vec<(usize, usize, i64)> = Vec::new();
+    for (i, row) in array.iter().enumerate().skip(1) {
+        for (j, &distance) in row.iter().enumerate().skip(i + 1) {
+            if distance < INFINITE {
+                edges.push((i - 1, j - 1, distance));
+            }
+        }
+    }
+    // Maximizing 'longer - distance' over a maximum cardinality matching minimizes the distance.
+    let longer = 1 + edges.iter().map(|&(_, _, distance)| distance).max().unwrap_or(0);
+    for edge in &mut edges {
+        edge.2 = longer - edge.2;
+    }
+
+    let mate = State::new(edges, n).run();
+
+    let mut pairs : Vec<(usize, usize)> = Vec::new();
+    for (v, &w) in mate.iter().enumerate() {
+        if w > v as isize {
+            pairs.push((v + 1, w as usize + 1));
+        }
+    }
+
+    let mut total_cost = 0;
+    for &(i, j) in &pairs {
+        let (from_name, _) = keys.get(&i).unwrap();
+        let (to_name, _) = keys.get(&j).unwrap();
+        info!("{} => {} ({})", Colour::Green.paint(from_name), Colour::Red.paint(to_name), array[i][j]);
+        total_cost += array[i][j];
+    }
+    if pairs.len() * 2 < n {
+        info!("{} odd nodes could not be paired ...", n - pairs.len() * 2);
+    }
+    info!("total cost = {}", total_cost);
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::munkres::square;
+    use crate::testing::Random;
+
+    // Symmetric distances between 'n' odd nodes (positions 1..), the same on every run.
+    #[allow(clippy::needless_range_loop)]
+    fn distances(seed : u64, n : usize) -> Matrix<i64> {
+        let mut random = Random(seed);
+        let mut array : Matrix<i64> = square(n + 1);
+        for i in 1..=n {
+            for j in i + 1..=n {
+                array[i][j] = 1 + random.next(500) as i64;
+                array[j][i] = array[i][j];
+            }
+        }
+        array
+    }
+
+    fn keys(n : usize) -> HashMap<usize, (String, usize)> {
+        (1..=n).map(|i| (i, (i.to_string(), i))).collect()
+    }
+
+    // Cheapest way to pair up all the nodes left in 'free'.
+    fn cheapest(array : & Matrix<i64>, free : & [usize]) -> i64 {
+        match free {
+            [] => 0,
+            [first, rest @ ..] => (0..rest.len())
+                .map(|k| {
+                    let others : Vec<usize> = rest.iter().enumerate().filter(|&(pos, _)| pos != k).map(|(_, &i)| i).collect();
+                    array[*first][rest[k]] + cheapest(array, &others)
+                })
+                .min()
+                .unwrap_or(INFINITE)
+        }
+    }
+
+    #[test]
+    fn perfect_minimum_matching() {
+        for n in (2..=10).step_by(2) {
+            for seed in 0..5 {
+                let array = distances(seed, n);
+                let pairs = solve(&array, &keys(n));
+
+                let mut seen = vec![false; n + 1];
+                for &(i, j) in &pairs {
+                    assert!(i < j && !seen[i] && !seen[j]);
+                    seen[i] = true;
+                    seen[j] = true;
+                }
+                assert_eq!(pairs.len() * 2, n);
+                let total : i64 = pairs.iter().map(|&(i, j)| array[i][j]).sum();
+                assert_eq!(total, cheapest(&array, &(1..=n).collect::<Vec<_>>()), "n = {}, seed = {}", n, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn unreachable_pairs_are_never_matched() {
+        // 1 and 2 cannot be joined, so each has to pair with one of the others.
+        let mut array = distances(3, 4);
+        array[1][2] = INFINITE;
+        array[2][1] = INFINITE;
+        let pairs = solve(&array, &keys(4));
+        assert_eq!(pairs.len(), 2);
+        assert!(pairs.iter().all(|&(i, j)| array[i][j] < INFINITE));
+    }
+}
@@ -1,5 +1,6 @@
 const SIZE : usize = 1000;
 
+#[allow(clippy::manual_memcpy)]
 fn write_row(array : &mut [[i32; SIZE]; SIZE], row : Vec<i32>, pos : usize) {
     for i in 0..row.len() {
         array[pos][i+1] = row[i];
@@ -0,0 +1,288 @@
+/**
+ * Command line arguments, e.g. 'route-builder -v solve alki.json --format json -o route.json'.
+ */
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use crate::attributes::Weight;
+use crate::components::Connector;
+use log::LevelFilter;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "route-builder", version, about = "Plans rides that cover every street of a map.")]
+pub struct Cli {
+    /// More logging; repeat for more detail (-v info, -vv debug, -vvv trace).
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose : u8,
+
+    /// Only log errors.
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet : bool,
+
+    /// Write the result to this file instead of stdout.
+    #[arg(short, long, global = true)]
+    pub output : Option<PathBuf>,
+
+    /// Format of the result.
+    #[arg(short, long, value_enum, default_value_t = Format::Text, global = true)]
+    pub format : Format,
+
+    #[command(subcommand)]
+    pub command : Command
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Pair up the odd intersections and build a circuit covering every street.
+    Solve(Solve),
+    /// Summarize a map without solving it.
+    Stats {
+        /// Map of intersections (JSON, or an .osm extract).
+        map : PathBuf,
+
+        #[command(flatten)]
+        lengths : Lengths
+    },
+    /// Write out the streets of a map.
+    Export {
+        /// Map of intersections (JSON, or an .osm extract).
+        map : PathBuf,
+
+        #[command(flatten)]
+        lengths : Lengths
+    },
+    /// List the pieces of a map that cannot reach each other.
+    Components {
+        /// Map of intersections (JSON, or an .osm extract).
+        map : PathBuf,
+
+        /// Join two intersections with a link that may be ridden but need not be, e.g. 'a=b'
+        /// (as the crow flies) or 'a=b:1200' (feet).
+        #[arg(long = "connect", value_name = "FROM=TO[:LENGTH]")]
+        connectors : Vec<Connector>,
+
+        #[command(flatten)]
+        lengths : Lengths
+    },
+    /// Check a map for bad data; exits with 1 if there are errors.
+    Validate {
+        /// Map of intersections (JSON, or an .osm extract).
+        map : PathBuf,
+
+        /// Treat warnings as errors.
+        #[arg(long)]
+        strict : bool,
+
+        /// Also report lengths further than this fraction of the distance between their
+        /// intersections from it, e.g. 0.5.
+        #[arg(long)]
+        tolerance : Option<f64>,
+
+        /// Measure distances between intersections along the slope, using their altitudes.
+        #[arg(long = "3d", requires = "tolerance")]
+        three_d : bool
+    },
+    /// Solve an assignment problem: a column for each row (or a row for each column, if there are
+    /// fewer), for the smallest total cost.
+    Assign {
+        /// JSON array of rows of costs (integers or decimals), 'null' where a row may not take
+        /// a column. The matrix need not be square: there may be more rows than columns, or fewer.
+        costs : PathBuf,
+        /// What the assignment makes as good as possible.
+        #[arg(long, value_enum, default_value_t = Objective::Minimize)]
+        objective : Objective,
+        /// List the K cheapest assignments instead, each with the pairs it does not share with
+        /// the cheapest.
+        #[arg(long, value_name = "K", default_value_t = 1, conflicts_with = "objective")]
+        best : usize,
+        /// How the smallest total is found; the other objectives always use Munkres' steps.
+        #[arg(long, value_enum, default_value_t = Algorithm::Munkres, conflicts_with_all = ["objective", "best"])]
+        algorithm : Algorithm
+    },
+    /// Convert an OpenStreetMap extract into a map of intersections (always JSON).
+    Import {
+        /// OpenStreetMap XML extract (.osm).
+        extract : PathBuf
+    }
+}
+
+#[derive(Args)]
+pub struct Solve {
+    /// Map of intersections (JSON, or an .osm extract).
+    pub map : PathBuf,
+
+    /// How neighbour links are read.
+    #[arg(long, value_enum, default_value_t = Mode::Undirected)]
+    pub mode : Mode,
+
+    /// Algorithm used to pair up the odd intersections (undirected mode only).
+    #[arg(long, value_enum, default_value_t = Matching::Blossom)]
+    pub matching : Matching,
+
+    /// Only streets listed in this file ('from to' per line) must be ridden (undirected mode only).
+    #[arg(long)]
+    pub required : Option<PathBuf>,
+
+    #[command(flatten)]
+    pub lengths : Lengths,
+
+    /// Extra cost per foot climbed; repeated streets are chosen to avoid hills.
+    #[arg(long, default_value_t = 0.0, value_parser = non_negative)]
+    pub uphill : f64,
+
+    /// Extra cost per foot descended (usually much less than '--uphill').
+    #[arg(long, default_value_t = 0.0, value_parser = non_negative)]
+    pub downhill : f64,
+
+    /// Multiply the cost of matching streets, e.g. 'surface=gravel:1.5' or 'bike_lane=yes:0.8'.
+    #[arg(long = "weight", value_name = "ATTRIBUTE=VALUE:FACTOR")]
+    pub weights : Vec<Weight>,
+
+    /// Extra cost of a left turn, which crosses the oncoming traffic.
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(i32).range(0..))]
+    pub left_turn : i32,
+
+    /// Extra cost of a right turn.
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(i32).range(0..))]
+    pub right_turn : i32,
+
+    /// Extra cost of turning back the way you came.
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(i32).range(0..))]
+    pub u_turn : i32,
+
+    /// Intersection the route starts at; with '--finish' the route no longer has to be closed.
+    #[arg(long, requires = "finish", conflicts_with = "home")]
+    pub start : Option<String>,
+
+    /// Intersection the route finishes at.
+    #[arg(long, requires = "start")]
+    pub finish : Option<String>,
+
+    /// Intersection every ride starts and finishes at; the result is then a list of rides.
+    #[arg(long)]
+    pub home : Option<String>,
+
+    /// Split the route into at most this many rides from '--home', keeping the longest short.
+    #[arg(long, requires = "home", conflicts_with = "max_distance")]
+    pub rides : Option<usize>,
+
+    /// Split the route into as few rides from '--home' as fit under this distance.
+    #[arg(long, requires = "home")]
+    pub max_distance : Option<i32>,
+
+    /// Join two intersections with a link that may be ridden but need not be, e.g. 'a=b'
+    /// (as the crow flies) or 'a=b:1200' (feet).
+    #[arg(long = "connect", value_name = "FROM=TO[:LENGTH]")]
+    pub connectors : Vec<Connector>,
+
+    /// What to do when the streets to ride are in pieces that cannot reach each other.
+    #[arg(long, value_enum, default_value_t = Disconnected::Refuse)]
+    pub disconnected : Disconnected,
+
+    /// Add a GPX waypoint wherever the route starts repeating a street.
+    #[arg(long)]
+    pub waypoints : bool
+}
+
+#[derive(Args, Clone, Copy)]
+pub struct Lengths {
+    /// Where street lengths come from.
+    #[arg(long = "lengths", value_enum, default_value_t = Source::Map)]
+    pub source : Source,
+
+    /// How far a listed length may be from the distance between its intersections, as a fraction
+    /// of that distance, before '--lengths checked' replaces it.
+    #[arg(long, default_value_t = 0.5)]
+    pub tolerance : f64,
+
+    /// Measure distances between intersections along the slope, using their altitudes.
+    #[arg(long = "3d")]
+    pub three_d : bool
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Source {
+    /// As listed in the map.
+    Map,
+    /// The distance between the two intersections, whatever the map lists.
+    Coordinates,
+    /// As listed, unless missing or too far from the distance between the intersections.
+    Checked
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Mode {
+    /// Every street can be ridden both ways.
+    Undirected,
+    /// Every neighbour link is one-way; two-way streets list each other.
+    Directed,
+    /// Links listed by both intersections are two-way unless flagged one-way, the rest one-way.
+    Mixed
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Disconnected {
+    /// Stop with an error.
+    Refuse,
+    /// Solve each piece on its own, as one ride per piece.
+    Separate
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Objective {
+    /// Smallest total cost.
+    Minimize,
+    /// Largest total, e.g. of preference scores.
+    Maximize,
+    /// Smallest worst single cost, then the smallest total.
+    Bottleneck
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    Text,
+    Json,
+    /// GPX 1.1 track, for loading onto bike computers.
+    Gpx,
+    /// GeoJSON feature collection, for GIS tools.
+    Geojson
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Matching {
+    /// Minimum weight perfect matching (exact).
+    Blossom,
+    /// Bipartite assignment, with conflicting pairs repaired greedily.
+    Munkres,
+    /// As 'munkres', solved by shortest augmenting paths in O(n³) for large maps.
+    JonkerVolgenant
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Algorithm {
+    /// The steps of Munkres' algorithm.
+    Munkres,
+    /// Shortest augmenting paths over row and column potentials, O(n³).
+    JonkerVolgenant
+}
+
+// A finite number that is not negative, such as a cost per foot.
+fn non_negative(text : &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(value) if value.is_finite() && value >= 0.0 => Ok(value),
+        _ => Err(format!("expected a finite number that is not negative, got '{}'", text))
+    }
+}
+
+impl Cli {
+    pub fn level(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::Error;
+        }
+        match self.verbose {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace
+        }
+    }
+}
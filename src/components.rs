@@ -0,0 +1,303 @@
+/**
+ * Connected components of the street network. Floyd-Warshall leaves 'INFINITE' between intersections
+ * that cannot reach each other, and those costs would otherwise flow silently into the matching.
+ * A map in several pieces is either refused, solved one piece at a time, or joined with
+ * connectors: links such as a ferry or a footpath that may be ridden between streets but never
+ * have to be covered themselves.
+ */
+use log::warn;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::geo::haversine;
+use crate::turns::Turns;
+use crate::{Map, INFINITE, Node, assemble, feet, index_map};
+
+// A link given on the command line as 'FROM=TO', or 'FROM=TO:LENGTH' in feet. Without a length
+// it is as long as the straight line between the two intersections.
+#[derive(Clone)]
+pub struct Connector {
+    pub from : String,
+    pub to : String,
+    pub length : Option<f64>
+}
+
+impl FromStr for Connector {
+    type Err = String;
+
+    fn from_str(text : &str) -> std::result::Result<Connector, String> {
+        let usage = || format!("expected FROM=TO or FROM=TO:LENGTH, got '{}'", text);
+        let (from, rest) = text.split_once('=').ok_or_else(usage)?;
+        let (to, length) = match rest.rsplit_once(':') {
+            Some((to, length)) => (to, Some(length.parse::<f64>().map_err(|_| usage())?)),
+            None => (rest, None)
+        };
+        if from.is_empty() || to.is_empty() || from == to {
+            return Err(usage());
+        }
+        if length.is_some_and(|length| !length.is_finite() || length < 0.0) {
+            return Err(format!("length must not be negative, got '{}'", text));
+        }
+        Ok(Connector { from: from.to_string(), to: to.to_string(), length })
+    }
+}
+
+// Checks the connectors name intersections on the map and works out their lengths.
+pub(crate) fn resolve(nodes : & [(usize, Node)], connectors : & [Connector]) -> Result<Vec<(String, String, i32)>> {
+    let ids = index_map(nodes);
+    connectors
+        .iter()
+        .map(|connector| {
+            let node = |name : &String| match ids.get(name) {
+                Some(&i) => Ok(&nodes[i - 1].1),
+                None => Err(Error::UnknownIntersection(name.clone()))
+            };
+            let (from, to) = (node(&connector.from)?, node(&connector.to)?);
+            let length = connector.length.unwrap_or_else(|| {
+                haversine((from.latitude, from.longitude), (to.latitude, to.longitude))
+            });
+            // At least a foot, like the streets, and refused if too long for the solvers.
+            match feet(length) {
+                Some(feet) => Ok((from.name.clone(), to.name.clone(), feet.max(1))),
+                None => Err(Error::Length { from: from.name.clone(), to: to.name.clone(), length: length.to_string() })
+            }
+        })
+        .collect()
+}
+
+pub(crate) struct Component {
+    // Rows of the all pairs matrix, in node order.
+    pub nodes : Vec<usize>,
+    pub streets : usize,
+    pub length : i32,
+    // South west and north east corners, as (latitude, longitude).
+    pub bounds : ((f64, f64), (f64, f64))
+}
+
+// The pieces of the map that can be reached from each other along streets (either way, whatever
+// the mode) or connectors, largest first. Intersections without any street are left out.
+pub(crate) fn find(map : & Map) -> Vec<Component> {
+    let ids = index_map(&map.nodes);
+    let size = map.nodes.len() + 1;
+    let mut adjacent : Vec<Vec<usize>> = vec![Vec::new(); size];
+    for (_, node) in &map.nodes {
+        for edge in node.edges.values() {
+            let (i, j) = (ids[&edge.from], ids[&edge.to]);
+            adjacent[i].push(j);
+            adjacent[j].push(i);
+        }
+    }
+    for &(i, j) in map.connectors.keys() {
+        adjacent[i].push(j);
+        adjacent[j].push(i);
+    }
+
+    let mut label : Vec<usize> = vec![0; size];
+    let mut components : Vec<Component> = Vec::new();
+    for root in 1..size {
+        if label[root] != 0 || adjacent[root].is_empty() {
+            continue;
+        }
+        label[root] = components.len() + 1;
+        let mut nodes = vec![root];
+        let mut stack = vec![root];
+        while let Some(i) = stack.pop() {
+            for &j in &adjacent[i] {
+                if label[j] == 0 {
+                    label[j] = label[root];
+                    nodes.push(j);
+                    stack.push(j);
+                }
+            }
+        }
+        nodes.sort();
+
+        let mut streets : HashSet<(usize, usize)> = HashSet::new();
+        let mut length = 0;
+        for &i in &nodes {
+            for edge in map.nodes[i - 1].1.edges.values() {
+                let j = ids[&edge.to];
+                if streets.insert((i.min(j), i.max(j))) {
+                    length += edge.length;
+                }
+            }
+        }
+        let point = |i : usize| (map.nodes[i - 1].1.latitude, map.nodes[i - 1].1.longitude);
+        let bounds = nodes.iter().fold(((f64::MAX, f64::MAX), (f64::MIN, f64::MIN)), |((s, w), (n, e)), &i| {
+            let (lat, lon) = point(i);
+            ((s.min(lat), w.min(lon)), (n.max(lat), e.max(lon)))
+        });
+        components.push(Component { nodes, streets: streets.len(), length, bounds });
+    }
+
+    // Only connectors, nothing to ride.
+    components.retain(|component| component.streets > 0);
+    components.sort_by(|a, b| b.nodes.len().cmp(&a.nodes.len()).then(a.nodes[0].cmp(&b.nodes[0])));
+    components
+}
+
+// Why a map in several pieces cannot be solved as one: no path between the first two.
+pub(crate) fn disconnected(map : & Map, components : & [Component]) -> Error {
+    let name = |component : &Component| map.nodes[component.nodes[0] - 1].1.name.clone();
+    Error::Disconnected { from: name(&components[0]), to: name(&components[1]) }
+}
+
+// A map of just one component, to be solved on its own.
+pub(crate) fn submap(map : & Map, component : & Component) -> Map {
+    let nodes : Vec<(usize, Node)> = component.nodes.iter().map(|&i| map.nodes[i - 1].clone()).collect();
+    let name = |i : usize| map.nodes[i - 1].1.name.clone();
+    let connectors : Vec<(String, String, i32)> = map.connectors
+        .iter()
+        .filter(|((i, _), _)| component.nodes.binary_search(i).is_ok())
+        .map(|(&(i, j), &length)| (name(i), name(j), length))
+        .collect();
+    assemble(nodes, &[], &connectors, map.oneway)
+}
+
+fn root(parent : &mut [usize], i : usize) -> usize {
+    let mut root = i;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    let mut i = i;
+    while parent[i] != root {
+        let up = parent[i];
+        parent[i] = root;
+        i = up;
+    }
+    root
+}
+
+// Groups the nodes touched by the (from, to, length) edges into connected pieces.
+pub(crate) fn pieces(size : usize, edges : & [(usize, usize, i32)]) -> Vec<Vec<usize>> {
+    let mut parent : Vec<usize> = (0..size).collect();
+    let mut touched : Vec<bool> = vec![false; size];
+    for &(from, to, _) in edges {
+        let (a, b) = (root(&mut parent, from), root(&mut parent, to));
+        parent[a] = b;
+        touched[from] = true;
+        touched[to] = true;
+    }
+    let mut groups : HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in (1..size).filter(|&i| touched[i]) {
+        let root = root(&mut parent, i);
+        groups.entry(root).or_default().push(i);
+    }
+    let mut pieces : Vec<Vec<usize>> = groups.into_values().collect();
+    pieces.sort();
+    pieces
+}
+
+// Closest pair of nodes between two pieces, and their distance.
+fn closest(a : & [usize], b : & [usize], cost : &impl Fn(usize, usize) -> i64) -> (usize, usize, i64) {
+    let mut best = (0, 0, INFINITE);
+    for &i in a {
+        for &j in b {
+            if cost(i, j) < best.2 {
+                best = (i, j, cost(i, j));
+            }
+        }
+    }
+    best
+}
+
+// Joins the pieces along a minimum spanning tree (Prim's), returning the connecting pairs.
+pub(crate) fn connect(pieces : & [Vec<usize>], cost : impl Fn(usize, usize) -> i64) -> Vec<(usize, usize)> {
+    let count = pieces.len();
+    if count < 2 {
+        return Vec::new();
+    }
+    let mut links : Vec<Vec<(usize, usize, i64)>> = vec![vec![(0, 0, INFINITE); count]; count];
+    for a in 0..count {
+        for b in (a + 1)..count {
+            links[a][b] = closest(&pieces[a], &pieces[b], &cost);
+            links[b][a] = links[a][b];
+        }
+    }
+
+    let mut joined : Vec<bool> = vec![false; count];
+    let mut best : Vec<(usize, usize, i64)> = links[0].clone();
+    joined[0] = true;
+    let mut pairs : Vec<(usize, usize)> = Vec::new();
+    for _ in 1..count {
+        let next = (0..count)
+            .filter(|&c| !joined[c])
+            .min_by_key(|&c| best[c].2)
+            .unwrap();
+        if best[next].2 >= INFINITE {
+            warn!("{} pieces cannot be reached from the rest ...", joined.iter().filter(|j| !**j).count());
+            break;
+        }
+        joined[next] = true;
+        pairs.push((best[next].0, best[next].1));
+        for c in 0..count {
+            if !joined[c] && links[next][c].2 < best[c].2 {
+                best[c] = links[next][c];
+            }
+        }
+    }
+    pairs
+}
+
+// Paths there and back joining the pieces of a balanced tour (as rows of the all pairs matrix),
+// which is how streets joined only by connectors end up in a single ride. Riding each path both
+// ways keeps every node balanced.
+pub(crate) fn round_trips(map : & Map, tour : & [(usize, usize, i32)], turns : & Turns) -> Vec<Vec<usize>> {
+    let pieces = pieces(map.nodes.len() + 1, tour);
+    let both = |i : usize, j : usize| if map.array[i][j] >= INFINITE || map.array[j][i] >= INFINITE { INFINITE } else { map.array[i][j] + map.array[j][i] };
+    connect(&pieces, both)
+        .into_iter()
+        .flat_map(|(i, j)| [turns.path(map, i, j), turns.path(map, j, i)])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cli::Matching;
+    use crate::testing::{both, nodes};
+
+    // Two triangles of 100 foot streets, 'a b c' and 'd e f', with nothing between them.
+    fn triangles() -> Vec<(usize, Node)> {
+        let intersections = [("a", 0.0, 0.0), ("b", 0.0, 0.001), ("c", 0.001, 0.0), ("d", 0.0, 0.01), ("e", 0.0, 0.011), ("f", 0.001, 0.01)];
+        nodes(&intersections, &both(&[("a", "b", 100), ("b", "c", 100), ("c", "a", 100), ("d", "e", 100), ("e", "f", 100), ("f", "d", 100)]))
+    }
+
+    #[test]
+    fn finds_the_pieces() {
+        let map = assemble(triangles(), &[], &[], false);
+        let pieces = find(&map);
+        assert_eq!(pieces.iter().map(|piece| piece.nodes.clone()).collect::<Vec<_>>(), vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        assert!(pieces.iter().all(|piece| piece.streets == 3 && piece.length == 300));
+        assert!(matches!(disconnected(&map, &pieces), Error::Disconnected { from, to } if from == "a" && to == "d"));
+
+        // Each piece can be solved on its own.
+        let submap = submap(&map, &pieces[1]);
+        assert_eq!(submap.nodes.iter().map(|(_, node)| node.name.as_str()).collect::<Vec<_>>(), vec!["d", "e", "f"]);
+    }
+
+    #[test]
+    fn connectors_join_the_pieces() {
+        let connector : Connector = "c=d:500".parse().unwrap();
+        assert!("c=c".parse::<Connector>().is_err());
+        assert!("c=d:-5".parse::<Connector>().is_err());
+
+        let nodes = triangles();
+        let connectors = resolve(&nodes, &[connector]).unwrap();
+        assert_eq!(connectors, vec![("c".to_string(), "d".to_string(), 500)]);
+        assert!(resolve(&nodes, &["c=x".parse().unwrap()]).is_err());
+        assert_eq!(resolve(&nodes, &["c=d:0".parse().unwrap()]).unwrap()[0].2, 1);
+        assert!(matches!(resolve(&nodes, &["c=d:1e300".parse().unwrap()]), Err(Error::Length { .. })));
+
+        let map = assemble(nodes, &[], &connectors, false);
+        assert_eq!(find(&map).len(), 1);
+        // Both triangles in one ride, over the connector and back.
+        let circuit = crate::undirected(&map, Matching::Blossom, None, &Turns::default()).unwrap();
+        assert_eq!(circuit.route.first(), circuit.route.last());
+        assert_eq!((circuit.distance, circuit.deadhead), (600 + 1000, 1000));
+    }
+}
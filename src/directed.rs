@@ -0,0 +1,285 @@
+/**
+ * Directed Chinese postman: every neighbour link is a one-way arc. Nodes with more arcs in than
+ * out (or the reverse) are balanced by repeating shortest paths chosen with a min-cost flow, then
+ * the directed Euler tour is walked with Hierholzer's algorithm.
+ */
+use log::{debug,info};
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+
+use crate::components;
+use crate::error::{Error, Result};
+use crate::euler::{Circuit, Multigraph, hierholzer, repeat};
+use crate::turns::Turns;
+use crate::{Map, INFINITE, index_map, wrong_way};
+
+// Min-cost flow with successive shortest paths (Dijkstra over reduced costs).
+pub(crate) struct Flow {
+    adjacent : Vec<Vec<usize>>,
+    // Arc 'a' and its residual 'a ^ 1' are stored next to each other.
+    to : Vec<usize>,
+    capacity : Vec<i64>,
+    cost : Vec<i64>
+}
+
+impl Flow {
+    pub(crate) fn new(size : usize) -> Flow {
+        Flow {
+            adjacent: vec![Vec::new(); size],
+            to: Vec::new(),
+            capacity: Vec::new(),
+            cost: Vec::new()
+        }
+    }
+
+    // Returns the id of the forward arc.
+    pub(crate) fn add(&mut self, from : usize, to : usize, capacity : i64, cost : i64) -> usize {
+        let id = self.to.len();
+        self.adjacent[from].push(id);
+        self.to.push(to);
+        self.capacity.push(capacity);
+        self.cost.push(cost);
+        self.adjacent[to].push(id + 1);
+        self.to.push(from);
+        self.capacity.push(0);
+        self.cost.push(-cost);
+        id
+    }
+
+    // Flow carried by a forward arc, which shows up as capacity on its residual.
+    pub(crate) fn carried(&self, arc : usize) -> i64 {
+        self.capacity[arc ^ 1]
+    }
+
+    // Pushes as much flow as possible from 'source' to 'sink'; returns (flow, cost).
+    pub(crate) fn solve(&mut self, source : usize, sink : usize) -> (i64, i64) {
+        let size = self.adjacent.len();
+        // All costs start non-negative, so zero potentials are valid.
+        let mut potential : Vec<i64> = vec![0; size];
+        let (mut flow, mut cost) = (0, 0);
+
+        loop {
+            let mut distance : Vec<i64> = vec![INFINITE; size];
+            let mut previous : Vec<usize> = vec![usize::MAX; size];
+            let mut heap = BinaryHeap::new();
+            distance[source] = 0;
+            heap.push(Reverse((0, source)));
+            while let Some(Reverse((d, node))) = heap.pop() {
+                if d > distance[node] {
+                    continue;
+                }
+                for &arc in &self.adjacent[node] {
+                    let next = self.to[arc];
+                    let reduced = self.cost[arc] + potential[node] - potential[next];
+                    if self.capacity[arc] > 0 && distance[node] + reduced < distance[next] {
+                        distance[next] = distance[node] + reduced;
+                        previous[next] = arc;
+                        heap.push(Reverse((distance[next], next)));
+                    }
+                }
+            }
+            if distance[sink] == INFINITE {
+                break;
+            }
+            for node in 0..size {
+                if distance[node] < INFINITE {
+                    potential[node] += distance[node];
+                }
+            }
+
+            // Bottleneck along the path, then augment.
+            let mut push = INFINITE;
+            let mut node = sink;
+            while node != source {
+                let arc = previous[node];
+                push = push.min(self.capacity[arc]);
+                node = self.to[arc ^ 1];
+            }
+            let mut node = sink;
+            while node != source {
+                let arc = previous[node];
+                self.capacity[arc] -= push;
+                self.capacity[arc ^ 1] += push;
+                cost += push * self.cost[arc];
+                node = self.to[arc ^ 1];
+            }
+            flow += push;
+        }
+
+        (flow, cost)
+    }
+}
+
+// Arcs indexed from 1 like the all pairs matrix: (from, to, length).
+fn arcs(map : & Map, ids : & HashMap<String, usize>) -> Vec<(usize, usize, i32)> {
+    let mut arcs = Vec::new();
+    for (_, node) in &map.nodes {
+        let mut edges : Vec<_> = node.edges.values().collect();
+        edges.sort_by(|a, b| a.to.cmp(&b.to));
+        for edge in edges.into_iter().filter(|edge| !wrong_way(&map.nodes, ids, edge)) {
+            arcs.push((ids[&edge.from], ids[&edge.to], edge.length));
+        }
+    }
+    arcs
+}
+
+// Repeated paths (as rows/columns of the all pairs matrix) that balance every node. Fails when
+// a node with surplus in-arcs cannot reach any node left with surplus out-arcs.
+fn balance(map : & Map, arcs : & [(usize, usize, i32)]) -> Result<Vec<(usize, usize)>> {
+    let size = map.nodes.len() + 1;
+    // In minus out; positive nodes must start extra paths, negative nodes must end them.
+    let mut delta : Vec<i64> = vec![0; size];
+    for &(from, to, _) in arcs {
+        delta[from] -= 1;
+        delta[to] += 1;
+    }
+
+    let starts : Vec<usize> = (1..size).filter(|&i| delta[i] > 0).collect();
+    let ends : Vec<usize> = (1..size).filter(|&i| delta[i] < 0).collect();
+    info!("found {} nodes with surplus in-arcs and {} with surplus out-arcs.", starts.len(), ends.len());
+
+    // 0 = source, 1.. = starts, then ends, then the sink.
+    let sink = starts.len() + ends.len() + 1;
+    let mut flow = Flow::new(sink + 1);
+    let mut links : Vec<(usize, usize, usize)> = Vec::new();
+    let mut sources : Vec<usize> = Vec::new();
+    let mut sinks : Vec<usize> = Vec::new();
+    for (s, &start) in starts.iter().enumerate() {
+        sources.push(flow.add(0, s + 1, delta[start], 0));
+        for (e, &end) in ends.iter().enumerate() {
+            if map.array[start][end] < INFINITE {
+                let arc = flow.add(s + 1, starts.len() + e + 1, INFINITE, map.array[start][end]);
+                links.push((arc, start, end));
+            }
+        }
+    }
+    for (e, &end) in ends.iter().enumerate() {
+        sinks.push(flow.add(starts.len() + e + 1, sink, -delta[end], 0));
+    }
+
+    let (total, cost) = flow.solve(0, sink);
+    let needed : i64 = starts.iter().map(|&i| delta[i]).sum();
+    if total < needed {
+        // Any start and end left over have no path between them, or the flow would use it.
+        let name = |i : usize| map.nodes[i - 1].1.name.clone();
+        let s = (0..starts.len()).find(|&s| flow.carried(sources[s]) < delta[starts[s]]).unwrap();
+        let e = (0..ends.len()).find(|&e| flow.carried(sinks[e]) < -delta[ends[e]]).unwrap();
+        return Err(Error::Disconnected { from: name(starts[s]), to: name(ends[e]) });
+    }
+    debug!("min-cost flow pushed {} for a cost of {}.", total, cost);
+
+    let mut repeats = Vec::new();
+    for (arc, start, end) in links {
+        for _ in 0..flow.carried(arc) {
+            repeats.push((start, end));
+        }
+    }
+    Ok(repeats)
+}
+
+pub(crate) fn circuit(map : & Map, turns : & Turns) -> Result<Circuit> {
+    let ids = index_map(&map.nodes);
+    let mut graph = Multigraph::directed(map.nodes.len() + 1);
+    for (from, to, length) in arcs(map, &ids) {
+        graph.add(from, to, length);
+    }
+    let distance : i32 = graph.edges.iter().map(|(_, _, length)| length).sum();
+
+    let mut deadhead = 0;
+    let mut repeated : Vec<Vec<String>> = Vec::new();
+    for (start, end) in balance(map, &graph.edges)? {
+        deadhead += repeat(map, &mut graph, turns.path(map, start, end), &mut repeated);
+    }
+    for path in components::round_trips(map, &graph.edges, turns) {
+        deadhead += repeat(map, &mut graph, path, &mut repeated);
+    }
+    info!("repeated {} paths for a deadhead of {}.", repeated.len(), deadhead);
+
+    let route = match graph.edges.first() {
+        Some(&(start, _, _)) => hierholzer(map, &graph, start, turns)?,
+        None => Vec::new()
+    };
+
+    Ok(Circuit {
+        route: route.into_iter().map(|i| map.nodes[i - 1].1.name.clone()).collect(),
+        distance: distance + deadhead,
+        deadhead,
+        repeated
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::munkres::{Matrix, Munkres};
+    use crate::testing::{Random, both, directed, length};
+
+    #[test]
+    fn flow_with_capacities() {
+        // Three units can leave 0. The cheap route only carries one of them; the shortcut
+        // carries another past the full arc '1 -> 3' to the dear one.
+        let mut flow = Flow::new(4);
+        flow.add(0, 1, 2, 1);
+        let cheap = flow.add(1, 3, 1, 1);
+        let shortcut = flow.add(1, 2, 1, 0);
+        let dear = flow.add(0, 2, 1, 9);
+        flow.add(2, 3, 5, 4);
+        assert_eq!(flow.solve(0, 3), (3, 2 + 5 + 13));
+        assert_eq!(flow.carried(cheap), 1);
+        assert_eq!(flow.carried(shortcut), 1);
+        assert_eq!(flow.carried(dear), 1);
+    }
+
+    #[test]
+    fn flow_matches_assignment() {
+        let mut random = Random(11);
+        for n in 1..=6 {
+            let costs : Matrix<Option<i64>> = (0..n)
+                .map(|_| (0..n).map(|_| Some(random.next(50) as i64)).collect())
+                .collect();
+            // Source, a node per row and per column, sink.
+            let (source, sink) = (0, 2 * n + 1);
+            let mut flow = Flow::new(2 * n + 2);
+            for (i, row) in costs.iter().enumerate() {
+                flow.add(source, 1 + i, 1, 0);
+                flow.add(1 + n + i, sink, 1, 0);
+                for (j, cost) in row.iter().enumerate() {
+                    flow.add(1 + i, 1 + n + j, 1, cost.unwrap());
+                }
+            }
+            assert_eq!(flow.solve(source, sink), (n as i64, Munkres::solve(&costs).unwrap().cost));
+        }
+    }
+
+    #[test]
+    fn covers_every_arc() {
+        // A one-way loop round a two-way street: 'b' is entered twice but only left once, so
+        // 'b -> c' has to be ridden again.
+        let mut links = both(&[("b", "c", 100)]);
+        links.extend([("a", "b", length(200)), ("c", "a", length(300))]);
+        let map = directed(&[("a", 0.0, 0.0), ("b", 0.0, 0.001), ("c", 0.001, 0.001)], &links);
+        let circuit = circuit(&map, &Turns::default()).unwrap();
+        assert_eq!(circuit.route.first(), circuit.route.last());
+        let steps : Vec<(&str, &str)> = circuit.route.windows(2).map(|step| (step[0].as_str(), step[1].as_str())).collect();
+        for (from, to) in [("a", "b"), ("b", "c"), ("c", "b"), ("c", "a")] {
+            assert!(steps.contains(&(from, to)), "{:?}", steps);
+        }
+        assert!(steps.iter().all(|step| links.iter().any(|(from, to, _)| (*from, *to) == *step)), "{:?}", steps);
+        assert_eq!((circuit.distance, circuit.deadhead), (700 + 100, 100));
+    }
+
+    #[test]
+    fn refuses_a_map_that_is_not_strongly_connected() {
+        // 'x' can be left for 'b' but never reached again.
+        let mut links = both(&[("a", "b", 100), ("a", "c", 100), ("b", "c", 100)]);
+        links.push(("x", "b", length(100)));
+        let map = directed(&[("a", 0.0, 0.0), ("b", 0.0, 0.001), ("c", 0.001, 0.0), ("x", 0.001, 0.001)], &links);
+        match circuit(&map, &Turns::default()) {
+            Err(Error::Disconnected { from, to }) => assert_eq!((from.as_str(), to.as_str()), ("b", "x")),
+            _ => panic!("expected no path from b to x")
+        }
+    }
+}
@@ -0,0 +1,68 @@
+/**
+ * Everything that can go wrong reading a map or solving it, so callers can report it (or
+ * recover) instead of panicking.
+ */
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    // A file could not be read.
+    Io { path : String, source : io::Error },
+    Json(serde_json::Error),
+    Osm(roxmltree::Error),
+    // A neighbour link whose length is not a number, or is negative or too long.
+    Length { from : String, to : String, length : String },
+    UnknownNeighbour { from : String, to : String },
+    // An intersection named on the command line that is not on the map.
+    UnknownIntersection(String),
+    // No path between two intersections that have to be joined.
+    Disconnected { from : String, to : String },
+    // A cost matrix of the wrong shape, or with costs too large to add up.
+    Matrix(String)
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "could not read {}: {}", path, source),
+            Error::Json(error) => write!(f, "invalid map JSON: {}", error),
+            Error::Osm(error) => write!(f, "invalid OSM extract: {}", error),
+            Error::Length { from, to, length } => write!(f, "length '{}' from {} to {} is not a number, or is negative or too long", length, from, to),
+            Error::UnknownNeighbour { from, to } => write!(f, "{} has an unknown neighbour {}", from, to),
+            Error::UnknownIntersection(name) => write!(f, "{} is not an intersection on the map", name),
+            Error::Disconnected { from, to } => write!(f, "there is no path from {} to {}", from, to),
+            Error::Matrix(problem) => write!(f, "invalid cost matrix: {}", problem)
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Json(error) => Some(error),
+            Error::Osm(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error : serde_json::Error) -> Error {
+        Error::Json(error)
+    }
+}
+
+impl From<roxmltree::Error> for Error {
+    fn from(error : roxmltree::Error) -> Error {
+        Error::Osm(error)
+    }
+}
+
+// Reads a whole file, remembering which one failed.
+pub fn read(path : &str) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|source| Error::Io { path: path.to_string(), source })
+}
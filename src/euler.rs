@@ -0,0 +1,284 @@
+/**
+ * Builds the ride: duplicates the shortest path between every matched pair of odd nodes,
+ * then walks the resulting (now even) multigraph with Hierholzer's algorithm. The directed,
+ * mixed and rural modes build their multigraphs with the same helpers and walk them the same way.
+ */
+use log::{debug,info,warn};
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::components;
+use crate::error::{Error, Result};
+use crate::munkres::{self, Matrix};
+use crate::turns::Turns;
+use crate::{Map, INFINITE, index_map, shortest_path, street, streets};
+
+pub struct Circuit {
+    // Ordered intersection names, starting and finishing at the same node unless the route is open.
+    pub route : Vec<String>,
+    // Total length ridden, including repeated streets.
+    pub distance : i32,
+    // Length of the repeated (deadhead) streets only.
+    pub deadhead : i32,
+    // Intersections along each repeated path, one path per matched pair.
+    pub repeated : Vec<Vec<String>>
+}
+
+pub(crate) struct Multigraph {
+    // (from, to, length), indexed from 1 like the all pairs matrix.
+    pub(crate) edges : Vec<(usize, usize, i32)>,
+    // Node => ids of the incident edges, or only of the outgoing ones if the graph is directed.
+    pub(crate) adjacent : Vec<Vec<usize>>,
+    directed : bool
+}
+
+impl Multigraph {
+    pub(crate) fn new(size : usize) -> Multigraph {
+        Multigraph {
+            edges: Vec::new(),
+            adjacent: vec![Vec::new(); size],
+            directed: false
+        }
+    }
+
+    // A multigraph of one-way arcs, ridden from 'from' to 'to' only.
+    pub(crate) fn directed(size : usize) -> Multigraph {
+        Multigraph { directed: true, ..Multigraph::new(size) }
+    }
+
+    pub(crate) fn add(&mut self, from : usize, to : usize, length : i32) {
+        let id = self.edges.len();
+        self.edges.push((from, to, length));
+        self.adjacent[from].push(id);
+        if !self.directed {
+            self.adjacent[to].push(id);
+        }
+    }
+}
+
+fn load(map : & Map, ids : & HashMap<String, usize>) -> Multigraph {
+    let mut graph = Multigraph::new(map.nodes.len() + 1);
+    for edge in streets(&map.nodes) {
+        graph.add(ids[&edge.from], ids[&edge.to], edge.length);
+    }
+    graph
+}
+
+// Rides 'path' (rows of the all pairs matrix) again: adds its streets to the graph and the path
+// to 'repeated'. Returns the length added.
+pub(crate) fn repeat(map : & Map, graph : &mut Multigraph, path : Vec<usize>, repeated : &mut Vec<Vec<String>>) -> i32 {
+    let mut length = 0;
+    for step in path.windows(2) {
+        let street = street(map, step[0], step[1]).unwrap_or(0);
+        graph.add(step[0], step[1], street);
+        length += street;
+    }
+    repeated.push(path.into_iter().map(|i| map.nodes[i - 1].1.name.clone()).collect());
+    length
+}
+
+// Costs between the 'odd' rows for the matching, offset by 1 like 'Map::odd', and the keys it
+// logs them by. 'cost' is given positions in 'odd'.
+pub(crate) fn pairing(map : & Map, odd : & [usize], cost : impl Fn(usize, usize) -> i64) -> (Matrix<i64>, HashMap<usize, (String, usize)>) {
+    let mut matrix : Matrix<i64> = munkres::square(odd.len() + 1);
+    let mut keys : HashMap<usize, (String, usize)> = HashMap::new();
+    for (a, &i) in odd.iter().enumerate() {
+        let node = &map.nodes[i - 1].1;
+        keys.insert(a + 1, (node.name.clone(), node.id));
+        for b in 0..odd.len() {
+            matrix[a + 1][b + 1] = if a == b { INFINITE } else { cost(a, b) };
+        }
+    }
+    (matrix, keys)
+}
+// Turns the odd positions returned by the matching into intersection names, making sure each
+// odd node is used exactly once. Nodes the matching left out (or paired twice) are paired
+// greedily with their nearest remaining odd node.
+fn repair(map : & Map, pairs : & [(usize, usize)]) -> Vec<(String, String)> {
+    let mut used : HashSet<usize> = HashSet::new();
+    let mut repaired : Vec<(usize, usize)> = Vec::new();
+
+    for &(i, j) in pairs {
+        if i == j || used.contains(&i) || used.contains(&j) {
+            debug!("dropping conflicting pair ({},{}) ...", i, j);
+            continue;
+        }
+        used.insert(i);
+        used.insert(j);
+        repaired.push((i, j));
+    }
+
+    let mut left : Vec<usize> = (1..map.odd.len()).filter(|i| !used.contains(i)).collect();
+    if !left.is_empty() {
+        warn!("matching left {} odd nodes unpaired, pairing greedily ...", left.len());
+    }
+    while left.len() > 1 {
+        let i = left.remove(0);
+        let (pos, _) = left
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, &j)| map.odd[i][j])
+            .unwrap();
+        let j = left.remove(pos);
+        repaired.push((i, j));
+    }
+
+    repaired
+        .into_iter()
+        .map(|(i, j)| (map.odd_ids[&i].0.clone(), map.odd_ids[&j].0.clone()))
+        .collect()
+}
+
+// Walks the multigraph from 'start', leaving each node along the unused edge with the smallest
+// turn penalty. Fails if an edge cannot be reached from 'start', or in a directed graph if the
+// walk cannot get back from it.
+pub(crate) fn hierholzer(map : & Map, graph : & Multigraph, start : usize, turns : & Turns) -> Result<Vec<usize>> {
+    let mut used : Vec<bool> = vec![false; graph.edges.len()];
+    let mut next : Vec<usize> = vec![0; graph.adjacent.len()];
+    // (node, the node it was reached from or 0).
+    let mut stack : Vec<(usize, usize)> = vec![(start, 0)];
+    let mut route : Vec<usize> = Vec::new();
+    let other = |id : usize, node : usize| {
+        let (from, to, _) = graph.edges[id];
+        if from == node { to } else { from }
+    };
+
+    while let Some(&(node, previous)) = stack.last() {
+        let adjacent = &graph.adjacent[node];
+        while next[node] < adjacent.len() && used[adjacent[next[node]]] {
+            next[node] += 1;
+        }
+        let best = adjacent[next[node]..]
+            .iter()
+            .filter(|&&id| !used[id])
+            .min_by_key(|&&id| turns.penalty(map, previous, node, other(id, node)));
+        match best {
+            None => {
+                route.push(node);
+                stack.pop();
+            },
+            Some(&id) => {
+                used[id] = true;
+                stack.push((other(id, node), node));
+            }
+        }
+    }
+
+    if let Some(id) = used.iter().position(|used| !*used) {
+        let name = |i : usize| map.nodes[i - 1].1.name.clone();
+        return Err(Error::Disconnected { from: name(start), to: name(graph.edges[id].0) });
+    }
+
+    route.reverse();
+    Ok(route)
+}
+
+// 'pairs' are positions in 'map.odd', as returned by the matching. The walk begins at 'start',
+// which must be the start of an open route if 'parse' was given one.
+pub(crate) fn circuit(map : & Map, pairs : & [(usize, usize)], start : Option<&str>, turns : & Turns) -> Result<Circuit> {
+    let ids = index_map(&map.nodes);
+    let mut graph = load(map, &ids);
+    let distance : i32 = graph.edges.iter().map(|(_, _, length)| length).sum();
+    let count = graph.edges.len();
+
+    let mut deadhead = 0;
+    let mut repeated : Vec<Vec<String>> = Vec::new();
+    for (from, to) in repair(map, pairs) {
+        let path = shortest_path(map, &from, &to, turns);
+        if path.is_empty() {
+            return Err(Error::Disconnected { from, to });
+        }
+        deadhead += repeat(map, &mut graph, path.iter().map(|name| ids[name]).collect(), &mut repeated);
+    }
+    for path in components::round_trips(map, &graph.edges, turns) {
+        deadhead += repeat(map, &mut graph, path, &mut repeated);
+    }
+    info!("duplicated {} streets for a deadhead of {}.", graph.edges.len() - count, deadhead);
+
+    let start = match start {
+        Some(name) => ids[name],
+        None => (1..graph.adjacent.len())
+            .find(|&i| !graph.adjacent[i].is_empty())
+            .unwrap_or(0)
+    };
+    let route = if start == 0 { Vec::new() } else { hierholzer(map, &graph, start, turns)? };
+
+    Ok(Circuit {
+        route: route.iter().map(|&i| map.nodes[i - 1].1.name.clone()).collect(),
+        distance: distance + deadhead,
+        deadhead,
+        repeated
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{assemble, path};
+    use crate::cli::Matching;
+    use crate::testing::{both, length, nodes, oneway};
+
+    #[test]
+    fn deadheads_the_wrong_way_of_one_way_streets() {
+        // 'x -> b' is one-way, but the undirected mode rides every street both ways, so the odd
+        // ends 'a' and 'x' can still be joined.
+        let intersections = [("a", 0.0, 0.0), ("b", 0.0, 0.001), ("x", 0.0, 0.002)];
+        let links = [("a", "b", length(100)), ("b", "a", length(100)), ("x", "b", oneway(50)), ("b", "x", length(50))];
+        let map = assemble(nodes(&intersections, &links), &[], &[], false);
+        let circuit = crate::undirected(&map, Matching::Blossom, None, &Turns::default()).unwrap();
+        assert_eq!(circuit.route.first(), circuit.route.last());
+        assert_eq!((circuit.distance, circuit.deadhead), (300, 150));
+
+        // The directed and mixed modes keep their paths to the legal direction.
+        let map = assemble(nodes(&intersections, &links), &[], &[], true);
+        assert!(path(&map.next, 1, 3).is_empty());
+        assert_eq!(path(&map.next, 3, 1), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn open_routes_start_and_finish_where_asked() {
+        let square = [("a", 0.0, 0.0), ("b", 0.0, 0.001), ("c", 0.001, 0.001), ("d", 0.001, 0.0)];
+        let streets = both(&[("a", "b", 100), ("b", "c", 100), ("c", "d", 100), ("d", "a", 100)]);
+
+        // Every corner is even, so one side has to be ridden twice to finish across from 'a'.
+        let map = assemble(nodes(&square, &streets), &["a", "c"], &[], false);
+        let circuit = crate::undirected(&map, Matching::Blossom, Some("a"), &Turns::default()).unwrap();
+        assert_eq!((circuit.route.first().unwrap().as_str(), circuit.route.last().unwrap().as_str()), ("a", "c"));
+        assert_eq!(circuit.route.len(), 7);
+        assert_eq!((circuit.distance, circuit.deadhead), (600, 200));
+
+        // Starting and finishing at the odd ends of a line needs nothing repeated.
+        let line = both(&[("a", "b", 100), ("b", "c", 100)]);
+        let map = assemble(nodes(&square[..3], &line), &["c", "a"], &[], false);
+        let circuit = crate::undirected(&map, Matching::Blossom, Some("c"), &Turns::default()).unwrap();
+        assert_eq!(circuit.route, vec!["c", "b", "a"]);
+        assert_eq!((circuit.distance, circuit.deadhead), (200, 0));
+    }
+
+    #[test]
+    fn counts_streets_listed_at_one_end() {
+        // A triangle listed one way round is already a circuit.
+        let triangle = [("a", 0.0, 0.0), ("b", 0.0, 0.001), ("c", 0.001, 0.0)];
+        let links = [("a", "b", length(100)), ("b", "c", length(100)), ("c", "a", length(100))];
+        let map = assemble(nodes(&triangle, &links), &[], &[], false);
+        assert!(map.odd_ids.is_empty());
+        assert_eq!(path(&map.next, 2, 1), vec![2, 1]);
+        let circuit = crate::undirected(&map, Matching::Blossom, None, &Turns::default()).unwrap();
+        assert_eq!(circuit.route.first(), circuit.route.last());
+        assert_eq!((circuit.distance, circuit.deadhead), (300, 0));
+    }
+
+    #[test]
+    fn refuses_odd_nodes_that_cannot_be_joined() {
+        // Finishing on the other street leaves 'b' and 'd' odd, with no path between them.
+        let intersections = [("a", 0.0, 0.0), ("b", 0.0, 0.001), ("c", 0.01, 0.0), ("d", 0.01, 0.001)];
+        let streets = both(&[("a", "b", 100), ("c", "d", 100)]);
+        let map = assemble(nodes(&intersections, &streets), &["a", "c"], &[], false);
+        match crate::undirected(&map, Matching::Blossom, Some("a"), &Turns::default()) {
+            Err(Error::Disconnected { from, to }) => assert_eq!((from.as_str(), to.as_str()), ("b", "d")),
+            _ => panic!("expected no path from b to d")
+        }
+    }
+}
@@ -0,0 +1,59 @@
+/**
+ * Distances on the earth's surface, and what climbing them costs. Map lengths are in feet,
+ * altitudes in metres.
+ */
+const EARTH_RADIUS : f64 = 6371008.8;
+const FEET_PER_METRE : f64 = 3.28084;
+
+// Great circle distance in feet between two (latitude, longitude) points in degrees.
+pub fn haversine(from : (f64, f64), to : (f64, f64)) -> f64 {
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin() * FEET_PER_METRE
+}
+
+// Straight line distance in feet between two (latitude, longitude, altitude) points, along the
+// slope between their altitudes (in metres) if 'three_d'.
+pub fn distance(from : (f64, f64, f64), to : (f64, f64, f64), three_d : bool) -> f64 {
+    let flat = haversine((from.0, from.1), (to.0, to.1));
+    if !three_d {
+        return flat;
+    }
+    let rise = (to.2 - from.2) * FEET_PER_METRE;
+    (flat * flat + rise * rise).sqrt()
+}
+
+// Whether a listed length is further from the straight line distance than 'tolerance' (as a
+// fraction of that distance), allowing a foot either way for rounding. Streets bend, so a
+// listed length is usually a little longer.
+pub fn disagrees(listed : f64, straight : f64, tolerance : f64) -> bool {
+    (listed - straight).abs() > tolerance * straight + 1.0
+}
+
+// Compass bearing in degrees (clockwise from north) from one (latitude, longitude) point to another.
+pub fn bearing(from : (f64, f64), to : (f64, f64)) -> f64 {
+    let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+    let delta = (to.1 - from.1).to_radians();
+    let y = delta.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * delta.cos();
+    (y.atan2(x).to_degrees() + 360.0) % 360.0
+}
+
+// Extra cost per foot climbed and per foot descended, on top of the length of a street.
+#[derive(Clone, Copy, Default)]
+pub struct Climbing {
+    pub uphill : f64,
+    pub downhill : f64
+}
+
+impl Climbing {
+    // Cost of riding a street 'length' feet long from altitude 'from' to altitude 'to'.
+    pub fn cost(&self, length : i32, from : f64, to : f64) -> i32 {
+        let rise = (to - from) * FEET_PER_METRE;
+        let penalty = if rise > 0.0 { self.uphill * rise } else { -self.downhill * rise };
+        // Summed in 'f64' and clamped, so a steep penalty cannot overflow.
+        (length as f64 + penalty.max(0.0)).round().min(i32::MAX as f64) as i32
+    }
+}
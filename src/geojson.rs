@@ -0,0 +1,112 @@
+/**
+ * Writes the street graph and solved routes as GeoJSON feature collections: https://geojson.org
+ */
+use serde_json::{json, Value};
+
+use crate::euler::Circuit;
+use crate::{Map, Node, attributes, index_map, ridden, streets};
+
+// GeoJSON positions are [longitude, latitude, altitude].
+fn position(node : & Node) -> Value {
+    json!([node.longitude, node.latitude, node.altitude])
+}
+
+fn line(nodes : & [(usize, Node)], names : & [String]) -> Value {
+    let ids = index_map(nodes);
+    Value::Array(names.iter().map(|name| position(&nodes[ids[name] - 1].1)).collect())
+}
+
+fn collection(features : Vec<Value>) -> String {
+    json!({
+        "type": "FeatureCollection",
+        "features": features
+    }).to_string()
+}
+
+// Every street as a LineString, followed by the odd intersections as Points.
+pub(crate) fn graph(map : & Map) -> String {
+    let mut features : Vec<Value> = streets(&map.nodes)
+        .into_iter()
+        .map(|edge| {
+            let mut properties = json!({
+                "kind": "street",
+                "from": edge.from,
+                "to": edge.to,
+                "length": edge.length
+            });
+            properties.as_object_mut().unwrap().extend(attributes(edge));
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": line(&map.nodes, &[edge.from.clone(), edge.to.clone()])
+                },
+                "properties": properties
+            })
+        })
+        .collect();
+
+    let ids = index_map(&map.nodes);
+    let mut odd : Vec<&(String, usize)> = map.odd_ids.values().collect();
+    odd.sort();
+    for (name, id) in odd {
+        let node = &map.nodes[ids[name] - 1].1;
+        features.push(json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Point",
+                "coordinates": position(node)
+            },
+            "properties": {
+                "kind": "odd",
+                "name": name,
+                "id": id,
+                "degree": node.edges.len()
+            }
+        }));
+    }
+
+    collection(features)
+}
+
+// Each ride as a single LineString, followed by its repeated (deadhead) paths.
+pub(crate) fn route(map : & Map, rides : & [Circuit]) -> String {
+    let ids = index_map(&map.nodes);
+    let mut features : Vec<Value> = Vec::new();
+    for (ride, circuit) in rides.iter().enumerate() {
+        features.push(json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "LineString",
+                "coordinates": line(&map.nodes, &circuit.route)
+            },
+            "properties": {
+                "kind": "route",
+                "ride": ride + 1,
+                "distance": circuit.distance,
+                "deadhead": circuit.deadhead
+            }
+        }));
+
+        for path in &circuit.repeated {
+            let rows : Vec<usize> = path.iter().map(|name| ids[name]).collect();
+            let length = ridden(map, &rows);
+            features.push(json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": line(&map.nodes, path)
+                },
+                "properties": {
+                    "kind": "deadhead",
+                    "ride": ride + 1,
+                    "from": path.first(),
+                    "to": path.last(),
+                    "length": length
+                }
+            }));
+        }
+    }
+
+    collection(features)
+}
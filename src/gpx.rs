@@ -0,0 +1,79 @@
+/**
+ * Writes routes as GPX 1.1 tracks, one track per ride: https://www.topografix.com/GPX/1/1/
+ */
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::{Node, index_map};
+
+fn escape(text : &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Positions in the route where the rider sets off down a street they have already ridden,
+// together with the intersection they are heading towards.
+fn repeats(route : & [String]) -> Vec<(usize, &str)> {
+    let mut ridden : HashSet<(&str, &str)> = HashSet::new();
+    let mut repeats : Vec<(usize, &str)> = Vec::new();
+    let mut repeating = false;
+    for (pos, step) in route.windows(2).enumerate() {
+        let (from, to) = (step[0].as_str(), step[1].as_str());
+        let street = if from < to { (from, to) } else { (to, from) };
+        if ridden.insert(street) {
+            repeating = false;
+        } else {
+            // Only mark the start of a run of repeated streets.
+            if !repeating {
+                repeats.push((pos, to));
+            }
+            repeating = true;
+        }
+    }
+    repeats
+}
+
+pub(crate) fn write(name : &str, routes : & [Vec<String>], nodes : & [(usize, Node)], waypoints : bool) -> String {
+    let ids = index_map(nodes);
+    let node = |name : &str| &nodes[ids[name] - 1].1;
+
+    let mut gpx = String::new();
+    gpx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    gpx.push_str("<gpx version=\"1.1\" creator=\"route-builder\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n");
+
+    // Waypoints must come before the tracks.
+    if waypoints {
+        for route in routes {
+            for (pos, towards) in repeats(route) {
+                let at = node(&route[pos]);
+                let _ = writeln!(gpx, "  <wpt lat=\"{}\" lon=\"{}\">", at.latitude, at.longitude);
+                let _ = writeln!(gpx, "    <ele>{}</ele>", at.altitude);
+                let _ = writeln!(gpx, "    <name>{}</name>", escape(&at.name));
+                let _ = writeln!(gpx, "    <desc>repeat towards {}</desc>", escape(towards));
+                gpx.push_str("  </wpt>\n");
+            }
+        }
+    }
+
+    for (ride, route) in routes.iter().enumerate() {
+        let title = if routes.len() > 1 { format!("{} ride {}", name, ride + 1) } else { name.to_string() };
+        gpx.push_str("  <trk>\n");
+        let _ = writeln!(gpx, "    <name>{}</name>", escape(&title));
+        gpx.push_str("    <trkseg>\n");
+        for name in route {
+            let at = node(name);
+            let _ = writeln!(gpx, "      <trkpt lat=\"{}\" lon=\"{}\">", at.latitude, at.longitude);
+            let _ = writeln!(gpx, "        <ele>{}</ele>", at.altitude);
+            let _ = writeln!(gpx, "        <name>{}</name>", escape(&at.name));
+            gpx.push_str("      </trkpt>\n");
+        }
+        gpx.push_str("    </trkseg>\n");
+        gpx.push_str("  </trk>\n");
+    }
+    gpx.push_str("</gpx>\n");
+
+    gpx
+}
@@ -1,19 +1,70 @@
+
 use std::fs;
+use std::io;
+use std::path::Path;
 use std::time::Instant;
 use std::collections::HashMap;
+use std::collections::HashSet;
+
+use log::{info,debug,warn,log_enabled,Level};
 
-use log::info;
-use log::debug;
+use ansi_term::Colour;
+use clap::Parser;
+use serde_json::json;
 
 use serde::{Deserialize, Serialize};
 
+pub mod attributes;
+use attributes::{Attributes, Link, Weight};
+
 pub mod cases;
 
+pub mod cli;
+
+pub mod components;
+use components::Connector;
+
+pub mod directed;
+
+pub mod error;
+use error::{Error, Result};
+use cli::{Algorithm, Cli, Command, Disconnected, Format, Lengths, Matching, Mode, Objective, Source};
+
+pub mod blossom;
+
+pub mod euler;
+
+pub mod geo;
+
+pub mod geojson;
+
+pub mod gpx;
+
+pub mod mixed;
+
 pub mod munkres;
-use munkres::Matrix;
 
+pub mod osm;
+
+pub mod rides;
+
+pub mod rural;
+
+#[cfg(test)]
+mod testing;
+
+pub mod turns;
+
+pub mod validate;
+use munkres::{Assignment, Cost, Matrix, Munkres};
+
+// Every street is shorter than this many feet.
 const MAX : i32 = 1000000;
 
+// Cost of the path between intersections that cannot reach each other. Paths are summed in 'i64',
+// so any number of streets short of 'MAX' adds up well below it, and two of it still fit.
+const INFINITE : i64 = i64::MAX / 4;
+
 #[derive(Serialize, Deserialize)]
 struct Intersection {
     name: String,
@@ -22,22 +73,51 @@ struct Intersection {
     altitude: f64,
     latitude: f64,
     longitude: f64,
-    neighbours: HashMap<String, String>
+    neighbours: HashMap<String, Link>
 }
 
+#[derive(Clone)]
 struct Edge {
     from: String,
     to: String,
-    length: i32
+    length: i32,
+    // Length plus the climbing penalty in this direction, times any attribute weights; what
+    // the solvers minimise.
+    cost: i32,
+    attributes: Attributes
 }
 
+#[derive(Clone)]
 struct Node {
     id: usize,
     name: String,
+    altitude: f64,
+    latitude: f64,
+    longitude: f64,
     edges: HashMap<String, Edge>
 }
 
-fn build_map(intersections : & Vec<Intersection>) -> Vec<(usize, Node)> {
+// Everything 'parse' derives from a map file.
+struct Map {
+    // Nodes sorted by id; the node at position 'p' is row/column 'p + 1' of 'array'.
+    nodes: Vec<(usize, Node)>,
+    // All pairs cheapest costs between nodes (lengths, unless climbing is penalised), 'INFINITE'
+    // if there is no path.
+    array: Matrix<i64>,
+    // Next node on the shortest path from row to column, 0 if there is none.
+    next: Matrix<usize>,
+    // Cheapest costs between the odd nodes only.
+    odd: Matrix<i64>,
+    // Position in 'odd' => (name, id) of the node.
+    odd_ids: HashMap<usize, (String, usize)>,
+    // Lengths of the connectors between rows (lower row first), which only carry deadheading.
+    connectors: HashMap<(usize, usize), i32>,
+    // Whether links listed against a one-way street are left out of the paths (directed and
+    // mixed modes); in undirected mode every street can be ridden both ways.
+    oneway: bool
+}
+
+fn build_map(intersections : & Vec<Intersection>, climbing : & geo::Climbing, weights : & [Weight], lengths : & Lengths) -> Result<Vec<(usize, Node)>> {
     let mut nodes: HashMap<String, Node> = HashMap::new();
 
     // Add all intersections first, then populate edges/streets.
@@ -45,127 +125,309 @@ fn build_map(intersections : & Vec<Intersection>) -> Vec<(usize, Node)> {
         if !nodes.contains_key(&intersection.name) {
             nodes.insert(intersection.name.clone(), Node {
                 id: intersection.id,
-                name: intersection.name.clone(), 
+                name: intersection.name.clone(),
+                altitude: intersection.altitude,
+                latitude: intersection.latitude,
+                longitude: intersection.longitude,
                 edges: HashMap::new()
             });
         }
     }
 
+    let points : HashMap<String, (f64, f64, f64)> = nodes
+        .values()
+        .map(|node| (node.name.clone(), (node.latitude, node.longitude, node.altitude)))
+        .collect();
+    let mut replaced = 0;
     for intersection in intersections {
-        for (name, length) in intersection.neighbours.iter() {
+        for (name, link) in intersection.neighbours.iter() {
+            if !points.contains_key(name) {
+                return Err(Error::UnknownNeighbour { from: intersection.name.clone(), to: name.clone() });
+            }
             match nodes.get_mut(&intersection.name) {
                 None => (),
                 Some(node) => {
+                    let listed = link.checked_length().and_then(feet);
+                    let to = points[name];
+                    let straight = geo::distance((node.latitude, node.longitude, node.altitude), to, lengths.three_d);
+                    // At least a foot, since a zero cost means no street in the all pairs matrix.
+                    let derived = feet(straight).map(|derived| derived.max(1));
+                    let invalid = |length : String| Error::Length { from: intersection.name.clone(), to: name.clone(), length };
+                    let length = match (lengths.source, listed) {
+                        (Source::Coordinates, _) => derived.ok_or_else(|| invalid(format!("{:.0}", straight)))?,
+                        (Source::Checked, Some(listed)) if !geo::disagrees(listed as f64, straight, lengths.tolerance) => listed,
+                        (Source::Checked, _) => {
+                            debug!("replacing length {:?} from {} to {} with {:?} ...", listed, intersection.name, name, derived);
+                            replaced += 1;
+                            derived.ok_or_else(|| invalid(format!("{:.0}", straight)))?
+                        },
+                        (Source::Map, Some(listed)) => listed,
+                        (Source::Map, None) => return Err(invalid(match link {
+                            Link::Length(text) => text.clone(),
+                            Link::Street { length, .. } => length.to_string()
+                        }))
+                    };
+                    let attributes = link.attributes();
+                    let cost = climbing.cost(length, node.altitude, to.2) as f64 * attributes::factor(&attributes, weights);
                     node.edges.insert(name.clone(), Edge {
                         from: intersection.name.clone(),
                         to: name.clone(),
-                        length: length.parse::<f32>().unwrap().ceil() as i32
+                        length,
+                        // Still a street however heavily weighted.
+                        cost: (cost.round() as i32).clamp(1, MAX - 1),
+                        attributes
                     });
                 }
             }
         }
     }
 
+    if replaced > 0 {
+        info!("replaced {} lengths that were missing or disagreed with the coordinates.", replaced);
+    }
+
     // Return sorted Vector.
     let mut vector : Vec<(usize, Node)> = Vec::new();
-    for (name, node) in nodes {
+    for (_, node) in nodes {
         vector.push((node.id, node));
     }
-    vector.sort_by(|a, b| a.0.cmp(&b.0));
-    
-    return vector;
+    vector.sort_by_key(|a| a.0);
+
+    Ok(vector)
+}
+
+// A length the solvers can use, rounded up to whole feet: a number that is not negative and is
+// shorter than 'MAX'.
+fn feet(length : f64) -> Option<i32> {
+    if length.is_finite() && length >= 0.0 && length.ceil() < MAX as f64 {
+        Some(length.ceil() as i32)
+    } else {
+        None
+    }
+}
+
+// Every street once, in node order, regardless of whether both ends list it as a neighbour.
+fn streets(nodes : & [(usize, Node)]) -> Vec<&Edge> {
+    let mut seen : HashSet<(&str, &str)> = HashSet::new();
+    let mut streets : Vec<&Edge> = Vec::new();
+    for (_, node) in nodes {
+        let mut edges : Vec<&Edge> = node.edges.values().collect();
+        edges.sort_by(|a, b| a.to.cmp(&b.to));
+        for edge in edges {
+            let key = if edge.from < edge.to { (edge.from.as_str(), edge.to.as_str()) } else { (edge.to.as_str(), edge.from.as_str()) };
+            if seen.insert(key) {
+                streets.push(edge);
+            }
+        }
+    }
+    streets
+}
+
+// The attributes a street was given, as JSON properties.
+fn attributes(edge : & Edge) -> serde_json::Map<String, serde_json::Value> {
+    match serde_json::to_value(&edge.attributes).unwrap() {
+        serde_json::Value::Object(map) => map,
+        _ => serde_json::Map::new()
+    }
+}
+
+// A link listed against a one-way street: the directed and mixed modes leave it out, and so do
+// their paths (see 'Map::oneway').
+fn wrong_way(nodes : & [(usize, Node)], ids : & HashMap<String, usize>, edge : & Edge) -> bool {
+    !edge.attributes.oneway && nodes[ids[&edge.to] - 1].1.edges
+        .get(&edge.from)
+        .is_some_and(|back| back.attributes.oneway)
 }
 
-fn all_pairs_shortest_arr(array : &mut Matrix<i32>, intersections : & Vec<(usize, Node)>) {
+// Maps each node name to its row/column in the all pairs matrix.
+fn index_map(intersections : & [(usize, Node)]) -> HashMap<String, usize> {
+    intersections
+        .iter()
+        .enumerate()
+        .map(|(pos, (_, node))| (node.name.clone(), pos + 1))
+        .collect()
+}
+
+fn all_pairs_shortest_arr(array : &mut Matrix<i64>, next : &mut Matrix<usize>, intersections : & [(usize, Node)], connectors : & HashMap<(usize, usize), i32>, oneway : bool) {
 
     let size = intersections.len() + 1;
-    let mut ids : HashMap<String, i32> = HashMap::new();
     for i in 1..size {
+        array[i][1..size].fill(INFINITE);
         array[i][i] = 0;
+        next[i][i] = i;
     }
 
-    let mut keys : Vec<String> = intersections
-        .iter()
-        .map(|(id, node)| node.name.to_string())
-        .collect();
-    keys.sort();
+    // This map offsets (increases) the positions by 1, so 0 => 1.
+    let ids = index_map(intersections);
 
-    // This block offsets (increases) the keys by 1, so 0 => 1.
-    let mut count = 1;
-    for key in keys {
-        ids.insert(key.clone(), count);
-        count = count + 1;
-    }
-    
     debug!("loading node map into matrix ...");
     for (_, from_node) in intersections {
-        for (_, edge) in &from_node.edges {
-            if !ids.contains_key(&edge.to) {
-                println!("id_not_contains {}", &edge.to);
+        for edge in from_node.edges.values() {
+            // Nobody deadheads the wrong way up a one-way street, unless every street is two-way.
+            if oneway && wrong_way(intersections, &ids, edge) {
+                continue;
             }
-            if !ids.contains_key(&edge.from) {
-                println!("id_not_contains {}", &edge.from);
+            // 'build_map' has already checked every neighbour exists.
+            let (i, j) = (ids[&edge.from], ids[&edge.to]);
+            array[i][j] = edge.cost as i64;
+            next[i][j] = j;
+        }
+    }
+    if !oneway {
+        // A street only one end lists can still be ridden back, at the same cost.
+        for edge in streets(intersections) {
+            let (i, j) = (ids[&edge.from], ids[&edge.to]);
+            if array[j][i] == INFINITE {
+                array[j][i] = edge.cost as i64;
+                next[j][i] = i;
             }
-            let i = *ids.get(&edge.from).unwrap();
-            let j = *ids.get(&edge.to).unwrap();
-            array[i as usize][j as usize] = edge.length;
         }
     }
-
-    for i in 1..size {
-        for j in 1..size {
-            // A zero value that is not a self reference.
-            if array[i][j] == 0 && i != j{
-                array[i][j] = MAX;
+    for (&(i, j), &length) in connectors {
+        for (a, b) in [(i, j), (j, i)] {
+            if (length as i64) < array[a][b] {
+                array[a][b] = length as i64;
+                next[a][b] = b;
             }
         }
     }
 
     debug!("pre-all_pairs_shortest::print_raw_matrix ...");
-    munkres::print_raw_matrix(&array);
+    if log_enabled!(Level::Debug) {
+        munkres::print_raw_matrix(array);
+    }
 
     for k in 1..size {
         for i in 1..size {
             for j in 1..size {
-                if array[i][j] > array[i][k] + array[k][j] {
-                    array[i][j] = array[i][k] + array[k][j];
+                let through = array[i][k] + array[k][j];
+                if array[i][j] > through {
+                    array[i][j] = through;
+                    next[i][j] = next[i][k];
                 }
             }
         }
     }
-    
+
     debug!("post-all_pairs_shortest::print_raw_matrix ...");
-    munkres::print_raw_matrix(&array);
+    if log_enabled!(Level::Debug) {
+        munkres::print_raw_matrix(array);
+    }
 
 }
 
-fn parse(filename : String) -> (Matrix<i32>, Matrix<i32>, HashMap<usize, (String, usize)>) {
+// Rows/columns of the all pairs matrix visited on the shortest path from 'from' to 'to',
+// including both ends. Empty if 'to' cannot be reached.
+fn path(next : & Matrix<usize>, from : usize, to : usize) -> Vec<usize> {
+    if next[from][to] == 0 {
+        return Vec::new();
+    }
+    let mut path = vec![from];
+    let mut current = from;
+    while current != to {
+        current = next[current][to];
+        path.push(current);
+    }
+    path
+}
 
-    let contents = fs::read_to_string(filename)
-        .expect("Something went wrong reading the file");
-    let intersections : Vec<Intersection> = serde_json::from_str(&contents).unwrap();
+// Intersection names on the cheapest path between two intersections (counting turns if they
+// cost anything), including both ends. Empty if either is unknown or 'to' cannot be reached.
+fn shortest_path(map : & Map, from : &str, to : &str, turns : & turns::Turns) -> Vec<String> {
+    let ids = index_map(&map.nodes);
+    match (ids.get(from), ids.get(to)) {
+        (Some(&i), Some(&j)) => turns.path(map, i, j)
+            .into_iter()
+            .map(|k| map.nodes[k - 1].1.name.clone())
+            .collect(),
+        _ => Vec::new()
+    }
+}
+
+// Length actually ridden along a path of rows of the all pairs matrix.
+fn ridden(map : & Map, path : & [usize]) -> i32 {
+    path.windows(2).map(|step| street(map, step[0], step[1]).unwrap_or(0)).sum()
+}
+
+// Length of the street between two rows of the all pairs matrix, whichever end lists it, or of
+// the connector between them.
+fn street(map : & Map, from : usize, to : usize) -> Option<i32> {
+    let (a, b) = (&map.nodes[from - 1].1, &map.nodes[to - 1].1);
+    a.edges.get(&b.name).or_else(|| b.edges.get(&a.name)).map(|edge| edge.length)
+        .or_else(|| map.connectors.get(&(from.min(to), from.max(to))).copied())
+}
+
+// Reads intersections from either a JSON map or an OpenStreetMap (.osm) extract.
+fn load(filename : &str) -> Result<Vec<Intersection>> {
+    let contents = error::read(filename)?;
+    if filename.ends_with(".osm") {
+        Ok(osm::read(&contents)?)
+    } else {
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+// 'ends' are the start and finish of an open route; they swap parity, so the matching leaves
+// them as the only odd nodes and the route becomes an Euler path between them. 'oneway' keeps
+// paths off the wrong way of one-way streets.
+fn parse(filename : String, ends : Option<(&str, &str)>, climbing : & geo::Climbing, weights : & [Weight], connectors : & [Connector], lengths : & Lengths, oneway : bool) -> Result<Map> {
+
+    let intersections = load(&filename)?;
     debug!("translating JSON into node map ...");
-    let nodes = build_map(&intersections);
+    let nodes = build_map(&intersections, climbing, weights, lengths)?;
+    let connectors = components::resolve(&nodes, connectors)?;
+
+    let mut open : Vec<&str> = Vec::new();
+    if let Some((start, finish)) = ends {
+        for name in [start, finish] {
+            if !nodes.iter().any(|(_, node)| node.name == name) {
+                return Err(Error::UnknownIntersection(name.to_string()));
+            }
+        }
+        if start != finish {
+            open = vec![start, finish];
+        }
+    }
+    Ok(assemble(nodes, &open, &connectors, oneway))
+}
+
+// Builds the all pairs and odd matrices over the nodes, joined by the (named) connectors.
+fn assemble(nodes : Vec<(usize, Node)>, open : & [&str], connectors : & [(String, String, i32)], oneway : bool) -> Map {
     let nodes_len = nodes.len();
-    
-    let mut array : Matrix<i32> = munkres::square(nodes_len+1);
+    let ids = index_map(&nodes);
+    let mut joined : HashMap<(usize, usize), i32> = HashMap::new();
+    for (from, to, length) in connectors {
+        let (i, j) = (ids[from], ids[to]);
+        let shortest = joined.entry((i.min(j), i.max(j))).or_insert(*length);
+        *shortest = (*shortest).min(*length);
+    }
+
+    let mut array : Matrix<i64> = munkres::square(nodes_len+1);
+    let mut next : Matrix<usize> = munkres::square(nodes_len+1);
 
     let before = Instant::now();
     debug!("starting all-pairs-shortest-path ...");
-    all_pairs_shortest_arr(&mut array, &nodes);
+    all_pairs_shortest_arr(&mut array, &mut next, &nodes, &joined, oneway);
     info!("floyd-warshall -> {:.2?}", before.elapsed());
 
     let mut odd_ids : HashMap<usize, (String, usize)> = HashMap::new();
-    
+
     // Translate the index into the array ('array') containing all nodes to
     // the position of the node in the odd array.
     let mut map : Vec<usize> = vec![0; nodes_len+1];
     let mut pos = 1;
-    for (_, node) in nodes.iter() {
-        if node.edges.len() % 2 == 1 {
-            map[pos] = node.id + 1;
+    // Streets count at both ends, whichever of them lists the street.
+    let mut degrees : Vec<usize> = vec![0; nodes_len+1];
+    for edge in streets(&nodes) {
+        degrees[ids[&edge.from]] += 1;
+        degrees[ids[&edge.to]] += 1;
+    }
+    for (index, (_, node)) in nodes.iter().enumerate() {
+        let degree = degrees[index + 1] + open.contains(&node.name.as_str()) as usize;
+        if degree % 2 == 1 {
+            map[pos] = index + 1;
             odd_ids.insert(pos, (node.name.clone(), node.id));
-            pos = pos + 1;
+            pos += 1;
         }
     }
 
@@ -174,36 +436,542 @@ fn parse(filename : String) -> (Matrix<i32>, Matrix<i32>, HashMap<usize, (String
     let mut odd = munkres::square(odd_len+1);
     info!("found {} odd nodes.", odd_len);
     // Add one in the appropriate places to ensure the 'odd' array effectively
-    // stars at index 1 instead of 0.
-    for i in 1..pos {
-        for j in 1..pos {
-            odd[i][j] = array[map[i]][map[j]];
+    // stars at index 1 instead of 0. Repeated streets can be ridden either way, so pair
+    // on the mean cost of both directions (climbing makes them differ).
+    for (i, row) in odd.iter_mut().enumerate().skip(1) {
+        for (j, cost) in row.iter_mut().enumerate().skip(1) {
+            let (there, back) = (array[map[i]][map[j]], array[map[j]][map[i]]);
+            *cost = if i == j || there >= INFINITE || back >= INFINITE { INFINITE } else { (there + back) / 2 };
+        }
+    }
+
+    Map { nodes, array, next, odd, odd_ids, connectors: joined, oneway }
+}
+
+// Writes the result to the requested file, or stdout.
+fn write(output : Option<&Path>, contents : String) -> io::Result<()> {
+    match output {
+        Some(path) => {
+            info!("writing: {}...", path.display());
+            fs::write(path, contents)
+        },
+        None => {
+            print!("{}", contents);
+            Ok(())
         }
-        for j in 1..pos {
-            odd[j][i] = array[map[j]][map[i]];
+    }
+}
+
+// 'Munkres::solve' or 'Munkres::jonker_volgenant', for the matching.
+type Solver = fn(& [Vec<Option<i64>>]) -> Result<Assignment<i64>>;
+
+// Assigns the odd nodes to each other with one of the generic solvers. 'array' is offset by 1
+// along each axis, with 'INFINITE' for pairs that cannot be joined; 'keys' names each row for the log.
+// The pairs are offset by 1 as well, and a 'Stalled' assignment leaves some odd nodes unassigned.
+fn assign_odd(array : & Matrix<i64>, keys : & HashMap<usize, (String, usize)>, solver : Solver) -> Result<Assignment<i64>> {
+
+    debug!("printing input weight matrix (pre-processed)...");
+    if log_enabled!(Level::Debug) {
+        munkres::print_raw_matrix(array);
+    }
+
+    let size = array.len().saturating_sub(1);
+    // Wider than the matrix, so the penalty for forbidden pairs has room.
+    let costs : Matrix<Option<i64>> = (1..=size)
+        .map(|i| (1..=size).map(|j| Some(array[i][j]).filter(|&cost| cost < INFINITE)).collect())
+        .collect();
+    let assignment = solver(&costs)?;
+    let pairs : Vec<(usize, usize)> = assignment.pairs.iter().map(|&(i, j)| (i + 1, j + 1)).collect();
+
+    let name = |k : usize| keys.get(&k).map_or_else(|| k.to_string(), |(name, _)| name.clone());
+    for &(i, j) in &pairs {
+        info!("{} => {} ({})", Colour::Green.paint(name(i)), Colour::Red.paint(name(j)), array[i][j]);
+    }
+    info!("total cost = {}", assignment.cost);
+    if log_enabled!(Level::Debug) {
+        let mut mask : Matrix<usize> = munkres::square(size + 1);
+        for &(i, j) in &pairs {
+            mask[i][j] = 1;
         }
+        munkres::print_matrix(mask, keys.clone());
     }
 
-    for i in 1..pos {
-        odd[i][i] = MAX;
+    Ok(Assignment { pairs, ..assignment })
+}
+
+// Pairs up the odd nodes and walks the undirected circuit, from 'start' if given.
+fn undirected(map : & Map, matching : Matching, start : Option<&str>, turns : & turns::Turns) -> Result<euler::Circuit> {
+    if log_enabled!(Level::Debug) {
+        munkres::print_raw_matrix(&map.odd);
     }
 
-    (array, odd, odd_ids)
+    let before = Instant::now();
+    info!("solving for matching...");
+    let pairs = match matching {
+        Matching::Blossom => {
+            let pairs = blossom::solve(&map.odd, &map.odd_ids);
+            info!("blossom -> {:.2?}", before.elapsed());
+            pairs
+        },
+        Matching::Munkres | Matching::JonkerVolgenant => {
+            let (name, solver) : (&str, Solver) = match matching {
+                Matching::JonkerVolgenant => ("jonker-volgenant", Munkres::jonker_volgenant),
+                _ => ("munkres", Munkres::solve)
+            };
+            match assign_odd(&map.odd, &map.odd_ids, solver) {
+                Ok(assignment) if assignment.status == munkres::Status::Solved => {
+                    info!("{} -> {:.2?}", name, before.elapsed());
+                    assignment.pairs
+                },
+                Ok(assignment) => {
+                    warn!("only {} of {} odd nodes could be assigned, falling back to blossom ...", assignment.pairs.len(), map.odd.len() - 1);
+                    blossom::solve(&map.odd, &map.odd_ids)
+                },
+                Err(error) => {
+                    warn!("{}, falling back to blossom ...", error);
+                    blossom::solve(&map.odd, &map.odd_ids)
+                }
+            }
+        }
+    };
+
+    let before = Instant::now();
+    info!("building euler circuit...");
+    let circuit = euler::circuit(map, &pairs, start, turns)?;
+    info!("hierholzer -> {:.2?}", before.elapsed());
+    Ok(circuit)
 }
 
-fn main() {
+fn solve(args : & cli::Solve, format : Format) -> Result<String> {
+    let file = &args.map;
+    info!("parsing: {}...", file.display());
+    let ends = args.start.as_deref().zip(args.finish.as_deref());
+    let climbing = geo::Climbing { uphill: args.uphill, downhill: args.downhill };
+    let map = parse(file.display().to_string(), ends, &climbing, &args.weights, &args.connectors, &args.lengths, args.mode != Mode::Undirected)?;
+    let turns = turns::Turns { left: args.left_turn, right: args.right_turn, uturn: args.u_turn };
+    let required = match &args.required {
+        Some(required) => Some(rural::read(&error::read(&required.display().to_string())?)),
+        None => None
+    };
 
-    env_logger::init();
+    // Only the pieces with streets that have to be ridden matter.
+    let ids = index_map(&map.nodes);
+    let pieces : Vec<components::Component> = components::find(&map)
+        .into_iter()
+        .filter(|piece| match &required {
+            Some(required) => required.iter().any(|(from, _)| ids.get(from).is_some_and(|i| piece.nodes.binary_search(i).is_ok())),
+            None => true
+        })
+        .collect();
+    if pieces.len() > 1 {
+        warn!("the streets to ride are in {} pieces that cannot reach each other; see 'components', '--connect' and '--disconnected' ...", pieces.len());
+        if args.disconnected == Disconnected::Refuse {
+            return Err(components::disconnected(&map, &pieces));
+        }
+        let circuits = pieces
+            .iter()
+            .map(|piece| {
+                let submap = components::submap(&map, piece);
+                // Each piece only rides its own required streets.
+                let names = index_map(&submap.nodes);
+                let required : Option<Vec<(String, String)>> = required.as_ref().map(|required| required
+                    .iter()
+                    .filter(|(from, to)| names.contains_key(from) && names.contains_key(to))
+                    .cloned()
+                    .collect());
+                circuit(args, &submap, required.as_deref(), &turns)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        return Ok(output(file, &map, &circuits, None, format, args.waypoints));
+    }
+
+    let circuit = circuit(args, &map, required.as_deref(), &turns)?;
+    info!("route covers {} intersections, distance = {}, deadhead = {}",
+        circuit.route.len(), circuit.distance, circuit.deadhead);
 
-    // Deserialize JSON into data structures.
-   
-    let file = "website-alternate.json";
-    info!("parsing: {}...", file);
-    
-    let (_, odd, ids) = parse(file.to_string());
-    munkres::print_raw_matrix(&odd);
+    let home = match &args.home {
+        Some(home) => home,
+        None => return Ok(output(file, &map, &[circuit], None, format, args.waypoints))
+    };
+    let limit = match (args.rides, args.max_distance) {
+        (_, Some(distance)) => rides::Limit::Distance(distance),
+        (rides, None) => rides::Limit::Rides(rides.unwrap_or(1))
+    };
     let before = Instant::now();
-    info!("solving for matching...");
-    munkres::solve(odd, ids);
-    info!("munkres -> {:.2?}", before.elapsed());
-  }
\ No newline at end of file
+    info!("splitting into rides from {}...", home);
+    let rides = rides::split(&map, &circuit, home, limit, &turns)?;
+    info!("rides -> {:.2?}", before.elapsed());
+    Ok(output(file, &map, &rides, Some(home), format, args.waypoints))
+}
+
+// Covers every street of the map (or just the required ones) in the chosen mode.
+fn circuit(args : & cli::Solve, map : & Map, required : Option<&[(String, String)]>, turns : & turns::Turns) -> Result<euler::Circuit> {
+    Ok(match args.mode {
+        Mode::Undirected => match required {
+            Some(required) => {
+                let before = Instant::now();
+                info!("covering required streets...");
+                let circuit = rural::circuit(map, required, turns)?;
+                info!("rural -> {:.2?}", before.elapsed());
+                circuit
+            },
+            None => undirected(map, args.matching, args.start.as_deref(), turns)?
+        },
+        Mode::Directed => {
+            let before = Instant::now();
+            info!("balancing directed arcs...");
+            let circuit = directed::circuit(map, turns)?;
+            info!("directed -> {:.2?}", before.elapsed());
+            circuit
+        },
+        Mode::Mixed => {
+            let before = Instant::now();
+            info!("orienting mixed streets...");
+            let circuit = mixed::circuit(map, turns)?;
+            info!("mixed -> {:.2?}", before.elapsed());
+            circuit
+        }
+    })
+}
+
+// Formats the solved circuit, or the rides when the route was split from 'home' or the map is
+// solved one piece at a time.
+fn output(file : &Path, map : & Map, rides : & [euler::Circuit], home : Option<&String>, format : Format, waypoints : bool) -> String {
+    let distance : i32 = rides.iter().map(|ride| ride.distance).sum();
+    let deadhead : i32 = rides.iter().map(|ride| ride.deadhead).sum();
+    match (format, home) {
+        (Format::Text, None) if rides.len() == 1 => rides[0].route.iter().map(|name| format!("{}\n", name)).collect(),
+        (Format::Text, _) => rides
+            .iter()
+            .enumerate()
+            .map(|(pos, ride)| format!("# ride {}: distance = {}, deadhead = {}\n{}",
+                pos + 1, ride.distance, ride.deadhead,
+                ride.route.iter().map(|name| format!("{}\n", name)).collect::<String>()))
+            .collect(),
+        (Format::Json, None) if rides.len() == 1 => json!({
+            "route": rides[0].route,
+            "distance": rides[0].distance,
+            "deadhead": rides[0].deadhead,
+            "repeated": rides[0].repeated
+        }).to_string(),
+        (Format::Json, home) => json!({
+            "home": home,
+            "rides": rides.iter().map(|ride| json!({
+                "route": ride.route,
+                "distance": ride.distance,
+                "deadhead": ride.deadhead,
+                "repeated": ride.repeated
+            })).collect::<Vec<_>>(),
+            "distance": distance,
+            "deadhead": deadhead
+        }).to_string(),
+        (Format::Gpx, _) => {
+            let name = file.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+            let routes : Vec<Vec<String>> = rides.iter().map(|ride| ride.route.clone()).collect();
+            gpx::write(&name, &routes, &map.nodes, waypoints)
+        },
+        (Format::Geojson, _) => geojson::route(map, rides)
+    }
+}
+
+fn stats(file : &Path, lengths : & Lengths, format : Format) -> Result<String> {
+    info!("parsing: {}...", file.display());
+    let map = parse(file.display().to_string(), None, &geo::Climbing::default(), &[], &[], lengths, false)?;
+    let streets = streets(&map.nodes);
+    let pieces = components::find(&map).len();
+    let length : i32 = streets.iter().map(|edge| edge.length).sum();
+    let longest = streets.iter().map(|edge| edge.length).max().unwrap_or(0);
+
+    Ok(match format {
+        Format::Text => format!("intersections: {}\nstreets: {}\nodd intersections: {}\ncomponents: {}\ntotal length: {}\nlongest street: {}\n",
+            map.nodes.len(), streets.len(), map.odd_ids.len(), pieces, length, longest),
+        Format::Json => json!({
+            "intersections": map.nodes.len(),
+            "streets": streets.len(),
+            "odd": map.odd_ids.len(),
+            "components": pieces,
+            "length": length,
+            "longest": longest
+        }).to_string(),
+        Format::Gpx | Format::Geojson => unreachable!("stats are only written as text or json")
+    })
+}
+
+fn export(file : &Path, lengths : & Lengths, format : Format) -> Result<String> {
+    info!("parsing: {}...", file.display());
+    let map = parse(file.display().to_string(), None, &geo::Climbing::default(), &[], &[], lengths, false)?;
+    let streets = streets(&map.nodes);
+
+    Ok(match format {
+        Format::Text => streets
+            .iter()
+            .map(|edge| format!("{} {} {}\n", edge.from, edge.to, edge.length))
+            .collect(),
+        Format::Json => json!(streets
+            .iter()
+            .map(|edge| {
+                let mut street = json!({ "from": edge.from, "to": edge.to, "length": edge.length });
+                street.as_object_mut().unwrap().extend(attributes(edge));
+                street
+            })
+            .collect::<Vec<_>>()).to_string(),
+        Format::Geojson => geojson::graph(&map),
+        Format::Gpx => unreachable!("gpx is only written for solve")
+    })
+}
+
+// Intersections listed per component in the text report before the rest are counted.
+const LISTED : usize = 5;
+
+fn components(file : &Path, connectors : & [Connector], lengths : & Lengths, format : Format) -> Result<String> {
+    info!("parsing: {}...", file.display());
+    let map = parse(file.display().to_string(), None, &geo::Climbing::default(), &[], connectors, lengths, false)?;
+    let pieces = components::find(&map);
+    let name = |i : &usize| map.nodes[i - 1].1.name.clone();
+
+    Ok(match format {
+        Format::Text => pieces
+            .iter()
+            .enumerate()
+            .map(|(pos, piece)| {
+                let ((south, west), (north, east)) = piece.bounds;
+                let mut names : Vec<String> = piece.nodes.iter().take(LISTED).map(name).collect();
+                if piece.nodes.len() > LISTED {
+                    names.push(format!("... ({} more)", piece.nodes.len() - LISTED));
+                }
+                format!("# component {}: intersections = {}, streets = {}, length = {}
+bounds: {},{} to {},{}
+{}
+",
+                    pos + 1, piece.nodes.len(), piece.streets, piece.length, south, west, north, east, names.join(", "))
+            })
+            .collect(),
+        Format::Json => json!(pieces
+            .iter()
+            .map(|piece| {
+                let ((south, west), (north, east)) = piece.bounds;
+                json!({
+                    "intersections": piece.nodes.iter().map(name).collect::<Vec<_>>(),
+                    "streets": piece.streets,
+                    "length": piece.length,
+                    "bounds": { "south": south, "west": west, "north": north, "east": east }
+                })
+            })
+            .collect::<Vec<_>>()).to_string(),
+        Format::Gpx | Format::Geojson => unreachable!("components are only written as text or json")
+    })
+}
+
+// The report, and whether the map passed.
+fn validate(file : &Path, strict : bool, geometry : Option<(f64, bool)>, format : Format) -> Result<(String, bool)> {
+    info!("validating: {}...", file.display());
+    let contents = error::read(&file.display().to_string())?;
+    let issues = validate::validate(&file.display().to_string(), &contents, geometry);
+    let errors = issues.iter().filter(|issue| issue.severity == validate::Severity::Error).count();
+    let warnings = issues.len() - errors;
+    let passed = errors == 0 && (!strict || warnings == 0);
+
+    let report = match format {
+        Format::Text => {
+            let mut report : String = issues
+                .iter()
+                .map(|issue| {
+                    let severity = match issue.severity {
+                        validate::Severity::Error => "error",
+                        validate::Severity::Warning => "warning"
+                    };
+                    match issue.line {
+                        Some(line) => format!("{}:{}: {}: {}\n", file.display(), line, severity, issue.message),
+                        None => format!("{}: {}: {}\n", file.display(), severity, issue.message)
+                    }
+                })
+                .collect();
+            report.push_str(&format!("{} errors, {} warnings\n", errors, warnings));
+            report
+        },
+        Format::Json => json!({
+            "issues": issues,
+            "errors": errors,
+            "warnings": warnings,
+            "passed": passed
+        }).to_string(),
+        Format::Gpx | Format::Geojson => unreachable!("validation is only written as text or json")
+    };
+    Ok((report, passed))
+}
+
+// Solves the assignment problem in 'file' as integers, or as decimals if any cost is one.
+fn assign(file : &Path, objective : Objective, best : usize, algorithm : Algorithm, format : Format) -> Result<String> {
+    let contents = error::read(&file.display().to_string())?;
+    match serde_json::from_str::<Matrix<Option<i64>>>(&contents) {
+        Ok(costs) => assignment(&costs, objective, best, algorithm, format),
+        Err(_) => assignment(&serde_json::from_str::<Matrix<Option<f64>>>(&contents)?, objective, best, algorithm, format)
+    }
+}
+
+fn assignment<C : Cost + Serialize>(costs : & [Vec<Option<C>>], objective : Objective, best : usize, algorithm : Algorithm, format : Format) -> Result<String> {
+    let before = Instant::now();
+    let ranked = match objective {
+        _ if best > 1 => Munkres::best(costs, best)?,
+        Objective::Minimize if algorithm == Algorithm::JonkerVolgenant => vec![Munkres::jonker_volgenant(costs)?],
+        Objective::Minimize => vec![Munkres::solve(costs)?],
+        Objective::Maximize => vec![Munkres::maximize(costs)?],
+        Objective::Bottleneck => vec![Munkres::bottleneck(costs)?]
+    };
+    match algorithm {
+        Algorithm::Munkres => info!("munkres -> {:.2?}", before.elapsed()),
+        Algorithm::JonkerVolgenant => info!("jonker-volgenant -> {:.2?}", before.elapsed())
+    }
+
+    Ok(match format {
+        Format::Text => {
+            let list = |indices : &[usize]| indices.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(" ");
+            let mut text = String::new();
+            for (rank, assignment) in ranked.iter().enumerate() {
+                if best > 1 {
+                    text.push_str(&format!("# assignment {}\n", rank + 1));
+                }
+                for &(i, j) in &assignment.pairs {
+                    if let Some(cost) = costs[i][j] {
+                        text.push_str(&format!("{} {} {}\n", i, j, cost));
+                    }
+                }
+                text.push_str(&format!("total cost: {}\n", assignment.cost));
+                if !assignment.unassigned_rows.is_empty() {
+                    text.push_str(&format!("unassigned rows: {}\n", list(&assignment.unassigned_rows)));
+                }
+                if !assignment.unassigned_columns.is_empty() {
+                    text.push_str(&format!("unassigned columns: {}\n", list(&assignment.unassigned_columns)));
+                }
+                if rank > 0 {
+                    let differs : Vec<String> = assignment.differs(&ranked[0]).iter().map(|(i, j)| format!("{} {}", i, j)).collect();
+                    text.push_str(&format!("differs from the best: {}\n", differs.join(", ")));
+                }
+            }
+            text
+        },
+        Format::Json => {
+            let object = |assignment : &Assignment<C>| json!({
+                "pairs": assignment.pairs,
+                "cost": assignment.cost,
+                "unassigned_rows": assignment.unassigned_rows,
+                "unassigned_columns": assignment.unassigned_columns,
+                "status": assignment.status
+            });
+            if best > 1 {
+                let assignments : Vec<serde_json::Value> = ranked
+                    .iter()
+                    .map(|assignment| {
+                        let mut value = object(assignment);
+                        value["differs"] = json!(assignment.differs(&ranked[0]));
+                        value
+                    })
+                    .collect();
+                json!({ "assignments": assignments }).to_string()
+            } else {
+                object(&ranked[0]).to_string()
+            }
+        },
+        Format::Gpx | Format::Geojson => unreachable!("assignments are only written as text or json")
+    })
+}
+
+fn import(file : &Path) -> Result<String> {
+    info!("importing: {}...", file.display());
+    let intersections = load(&file.display().to_string())?;
+    Ok(serde_json::to_string_pretty(&intersections)?)
+}
+
+fn main() {
+
+    let cli = Cli::parse();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(cli.level().as_str()))
+        .init();
+
+    let supported = match cli.command {
+        Command::Solve(_) => true,
+        Command::Stats { .. } | Command::Components { .. } => matches!(cli.format, Format::Text | Format::Json),
+        Command::Export { .. } => cli.format != Format::Gpx,
+        Command::Validate { .. } | Command::Assign { .. } | Command::Import { .. } => matches!(cli.format, Format::Text | Format::Json)
+    };
+    if !supported {
+        eprintln!("this output format is not available for this command");
+        std::process::exit(2);
+    }
+    if let Command::Solve(args) = &cli.command {
+        if args.required.is_some() && args.mode != Mode::Undirected {
+            eprintln!("required streets are only supported in undirected mode");
+            std::process::exit(2);
+        }
+        if args.start.is_some() && (args.required.is_some() || args.mode != Mode::Undirected) {
+            eprintln!("open routes are only supported in undirected mode over every street");
+            std::process::exit(2);
+        }
+        if args.disconnected == Disconnected::Separate && (args.start.is_some() || args.home.is_some()) {
+            eprintln!("separate pieces are each solved as a closed route, without a start or home");
+            std::process::exit(2);
+        }
+    }
+
+    let mut passed = true;
+    let result = match &cli.command {
+        Command::Solve(args) => solve(args, cli.format),
+        Command::Stats { map, lengths } => stats(map, lengths, cli.format),
+        Command::Export { map, lengths } => export(map, lengths, cli.format),
+        Command::Components { map, connectors, lengths } => components(map, connectors, lengths, cli.format),
+        Command::Validate { map, strict, tolerance, three_d } => validate(map, *strict, tolerance.map(|tolerance| (tolerance, *three_d)), cli.format).map(|(report, ok)| {
+            passed = ok;
+            report
+        }),
+        Command::Assign { costs, objective, best, algorithm } => assign(costs, *objective, *best, *algorithm, cli.format),
+        Command::Import { extract } => import(extract)
+    };
+    let contents = match result {
+        Ok(contents) => contents,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(error) = write(cli.output.as_deref(), contents) {
+        eprintln!("could not write output: {}", error);
+        std::process::exit(1);
+    }
+    if !passed {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+
+    #[test]
+    fn refuses_lengths_out_of_range() {
+        let lengths = Lengths { source: Source::Map, tolerance: 0.5, three_d: false };
+        for feet in ["-50", "3000000000", "inf", "NaN"] {
+            let intersection = |name : &str, neighbour : &str| Intersection {
+                name: name.to_string(),
+                address: String::new(),
+                id: 1,
+                altitude: 0.0,
+                latitude: 0.0,
+                longitude: 0.0,
+                neighbours: HashMap::from([(neighbour.to_string(), Link::Length(feet.to_string()))])
+            };
+            let intersections = vec![intersection("a", "b"), intersection("b", "a")];
+            match build_map(&intersections, &geo::Climbing::default(), &[], &lengths) {
+                Err(Error::Length { length, .. }) => assert_eq!(length, feet),
+                _ => panic!("expected {} to be refused", feet)
+            }
+        }
+        // The longest streets there can be still add up along a path.
+        let intersections = [("a", 0.0, 0.0), ("b", 0.0, 0.001), ("c", 0.0, 0.002)];
+        let map = testing::map(&intersections, &testing::both(&[("a", "b", MAX - 1), ("b", "c", MAX - 1)]));
+        assert_eq!(map.array[1][2], (MAX - 1) as i64);
+        assert_eq!(map.array[1][3], 2 * (MAX - 1) as i64);
+    }
+}
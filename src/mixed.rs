@@ -0,0 +1,281 @@
+/**
+ * Mixed Chinese postman for maps with both one-way and two-way streets, using the MIXED-2 style
+ * heuristic (Frederickson, 1979):
+ *  1. A min-cost flow orients two-way streets and repeats streets until every node has as many
+ *     arcs in as out.
+ *  2. Two-way streets the flow left unoriented are made even by pairing their odd nodes (blossom
+ *     matching over those streets only) and repeating the matched paths.
+ *  3. The remaining two-way streets are oriented along Euler cycles and the directed tour walked.
+ *
+ * A link is two-way when both intersections list each other and neither is flagged 'oneway',
+ * and one-way otherwise.
+ */
+use log::{debug,info};
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+
+use crate::blossom;
+use crate::components;
+use crate::directed::Flow;
+use crate::error::{Error, Result};
+use crate::euler::{Circuit, Multigraph, hierholzer, pairing, repeat};
+use crate::turns::Turns;
+use crate::{Map, INFINITE, index_map, wrong_way};
+
+// (from, to, length), indexed from 1 like the all pairs matrix.
+type Links = Vec<(usize, usize, i32)>;
+
+// Splits the links into one-way arcs and two-way streets (listed once, smaller index first).
+fn links(map : & Map, ids : & HashMap<String, usize>) -> (Links, Links) {
+    let mut arcs = Vec::new();
+    let mut streets = Vec::new();
+    for (_, node) in &map.nodes {
+        let mut edges : Vec<_> = node.edges.values().collect();
+        edges.sort_by(|a, b| a.to.cmp(&b.to));
+        for edge in edges.into_iter().filter(|edge| !wrong_way(&map.nodes, ids, edge)) {
+            let (i, j) = (ids[&edge.from], ids[&edge.to]);
+            match map.nodes[j - 1].1.edges.get(&edge.from) {
+                Some(back) if !edge.attributes.oneway && !back.attributes.oneway => {
+                    if i < j {
+                        streets.push((i, j, edge.length));
+                    }
+                },
+                _ => arcs.push((i, j, edge.length))
+            }
+        }
+    }
+    (arcs, streets)
+}
+
+// Shortest paths from 'from' over the given two-way streets only; returns (distance, previous).
+fn dijkstra(size : usize, adjacent : & [Vec<(usize, i32)>], from : usize) -> (Vec<i64>, Vec<usize>) {
+    let mut distance : Vec<i64> = vec![INFINITE; size];
+    let mut previous : Vec<usize> = vec![0; size];
+    let mut heap = BinaryHeap::new();
+    distance[from] = 0;
+    heap.push(Reverse((0, from)));
+    while let Some(Reverse((d, node))) = heap.pop() {
+        if d > distance[node] {
+            continue;
+        }
+        for &(next, length) in &adjacent[node] {
+            if d + (length as i64) < distance[next] {
+                distance[next] = d + length as i64;
+                previous[next] = node;
+                heap.push(Reverse((distance[next], next)));
+            }
+        }
+    }
+    (distance, previous)
+}
+
+// Orients two-way streets so that every node is entered as often as it is left. Each node
+// has an even number of such streets, so they split into cycles.
+fn orient(size : usize, streets : & [(usize, usize, i32)]) -> Vec<(usize, usize, i32)> {
+    let mut adjacent : Vec<Vec<usize>> = vec![Vec::new(); size];
+    for (id, &(i, j, _)) in streets.iter().enumerate() {
+        adjacent[i].push(id);
+        adjacent[j].push(id);
+    }
+    let mut used : Vec<bool> = vec![false; streets.len()];
+    let mut next : Vec<usize> = vec![0; size];
+    let mut oriented = Vec::new();
+
+    for start in 1..size {
+        let mut stack : Vec<(usize, usize)> = vec![(start, usize::MAX)];
+        while let Some(&(node, via)) = stack.last() {
+            while next[node] < adjacent[node].len() && used[adjacent[node][next[node]]] {
+                next[node] += 1;
+            }
+            if next[node] < adjacent[node].len() {
+                let id = adjacent[node][next[node]];
+                used[id] = true;
+                let (i, j, _) = streets[id];
+                stack.push((if i == node { j } else { i }, id));
+            } else {
+                stack.pop();
+                if let (Some(&(from, _)), true) = (stack.last(), via != usize::MAX) {
+                    oriented.push((from, node, streets[via].2));
+                }
+            }
+        }
+    }
+    oriented
+}
+
+pub(crate) fn circuit(map : & Map, turns : & Turns) -> Result<Circuit> {
+    let ids = index_map(&map.nodes);
+    let size = map.nodes.len() + 1;
+    let name = |i : usize| map.nodes[i - 1].1.name.clone();
+    let (arcs, streets) = links(map, &ids);
+    info!("found {} one-way and {} two-way streets.", arcs.len(), streets.len());
+    let distance : i32 = arcs.iter().chain(streets.iter()).map(|(_, _, length)| length).sum();
+
+    // 1. In minus out over the one-way arcs; the flow runs from surplus in to surplus out.
+    let mut delta : Vec<i64> = vec![0; size];
+    for &(from, to, _) in &arcs {
+        delta[from] -= 1;
+        delta[to] += 1;
+    }
+    let sink = size;
+    let mut flow = Flow::new(size + 1);
+    // (arc from the source or to the sink, node).
+    let mut surplus : Vec<(usize, usize)> = Vec::new();
+    for (v, &delta) in delta.iter().enumerate().skip(1) {
+        if delta > 0 {
+            surplus.push((flow.add(0, v, delta, 0), v));
+        } else if delta < 0 {
+            surplus.push((flow.add(v, sink, -delta, 0), v));
+        }
+    }
+    // Repeating any street costs its length (plus climbing); riding a two-way street the first
+    // time is free in whichever direction.
+    let cost = |i : usize, j : usize| map.nodes[i - 1].1.edges[&map.nodes[j - 1].1.name].cost as i64;
+    let mut copies : Vec<(usize, usize, usize, i32)> = Vec::new();
+    for &(i, j, length) in &arcs {
+        copies.push((flow.add(i, j, INFINITE, cost(i, j)), i, j, length));
+    }
+    for &(i, j, length) in &streets {
+        copies.push((flow.add(i, j, INFINITE, cost(i, j)), i, j, length));
+        copies.push((flow.add(j, i, INFINITE, cost(j, i)), j, i, length));
+    }
+    let free : Vec<(usize, usize)> = streets
+        .iter()
+        .map(|&(i, j, _)| (flow.add(i, j, 1, 0), flow.add(j, i, 1, 0)))
+        .collect();
+    let (total, cost) = flow.solve(0, sink);
+    let needed : i64 = delta.iter().filter(|&&d| d > 0).sum();
+    if total < needed {
+        // Any node left with surplus in-arcs has no path to one left with surplus out-arcs, or the
+        // flow would use it.
+        let left = |sign : i64| surplus
+            .iter()
+            .find(|&&(arc, v)| delta[v].signum() == sign && flow.carried(arc) < delta[v].abs())
+            .map(|&(_, v)| v)
+            .unwrap();
+        return Err(Error::Disconnected { from: name(left(1)), to: name(left(-1)) });
+    }
+    debug!("min-cost flow pushed {} for a cost of {}.", total, cost);
+
+    let mut tour : Vec<(usize, usize, i32)> = arcs.clone();
+    let mut deadhead = 0;
+    let mut repeated : Vec<Vec<String>> = Vec::new();
+    for &(arc, i, j, length) in &copies {
+        for _ in 0..flow.carried(arc) {
+            tour.push((i, j, length));
+            deadhead += length;
+            repeated.push(vec![name(i), name(j)]);
+        }
+    }
+    let mut unoriented : Vec<(usize, usize, i32)> = Vec::new();
+    for (&(forward, backward), &(i, j, length)) in free.iter().zip(streets.iter()) {
+        match (flow.carried(forward), flow.carried(backward)) {
+            (1, 0) => tour.push((i, j, length)),
+            (0, 1) => tour.push((j, i, length)),
+            // Unused, or used both ways (which cancels out).
+            _ => unoriented.push((i, j, length))
+        }
+    }
+    info!("oriented {} two-way streets, {} left unoriented.", streets.len() - unoriented.len(), unoriented.len());
+
+    // 2. Pair up nodes with an odd number of unoriented streets.
+    let mut degree : Vec<usize> = vec![0; size];
+    let mut adjacent : Vec<Vec<(usize, i32)>> = vec![Vec::new(); size];
+    for &(i, j, length) in &unoriented {
+        degree[i] += 1;
+        degree[j] += 1;
+        adjacent[i].push((j, length));
+        adjacent[j].push((i, length));
+    }
+    let odd : Vec<usize> = (1..size).filter(|&i| degree[i] % 2 == 1).collect();
+    if !odd.is_empty() {
+        let paths : Vec<(Vec<i64>, Vec<usize>)> = odd.iter().map(|&i| dijkstra(size, &adjacent, i)).collect();
+        let (matrix, keys) = pairing(map, &odd, |a, b| paths[a].0[odd[b]]);
+        for (a, b) in blossom::solve(&matrix, &keys) {
+            let (to, previous) = (odd[b - 1], &paths[a - 1].1);
+            let mut path = vec![to];
+            let mut node = to;
+            while node != odd[a - 1] {
+                let back = previous[node];
+                let length = adjacent[node].iter().filter(|(n, _)| *n == back).map(|(_, l)| *l).min().unwrap();
+                unoriented.push((back, node, length));
+                deadhead += length;
+                node = back;
+                path.push(node);
+            }
+            path.reverse();
+            repeated.push(path.into_iter().map(name).collect());
+        }
+    }
+
+    // 3. Orient what is left along cycles, then walk the directed tour.
+    tour.extend(orient(size, &unoriented));
+    let mut graph = Multigraph::directed(size);
+    for (from, to, length) in tour {
+        graph.add(from, to, length);
+    }
+    for path in components::round_trips(map, &graph.edges, turns) {
+        deadhead += repeat(map, &mut graph, path, &mut repeated);
+    }
+    info!("repeated {} streets or paths for a deadhead of {}.", repeated.len(), deadhead);
+
+    let route = match graph.edges.first() {
+        Some(&(start, _, _)) => hierholzer(map, &graph, start, turns)?,
+        None => Vec::new()
+    };
+
+    Ok(Circuit {
+        route: route.into_iter().map(name).collect(),
+        distance: distance + deadhead,
+        deadhead,
+        repeated
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::attributes::Link;
+    use crate::testing::{both, directed, length};
+
+    #[test]
+    fn covers_every_street_legally() {
+        // A square of two-way streets crossed by two one-way diagonals.
+        let mut links = both(&[("a", "b", 100), ("b", "c", 100), ("c", "d", 100), ("d", "a", 100)]);
+        links.extend([("a", "c", length(140)), ("d", "b", length(140))]);
+        let map = directed(&[("a", 0.0, 0.0), ("b", 0.0, 0.001), ("c", 0.001, 0.001), ("d", 0.001, 0.0)], &links);
+        let circuit = circuit(&map, &Turns::default()).unwrap();
+
+        assert_eq!(circuit.route.first(), circuit.route.last());
+        let steps : Vec<(&str, &str)> = circuit.route.windows(2).map(|step| (step[0].as_str(), step[1].as_str())).collect();
+        // Every step is a link in the direction it is listed, so one-way streets are never ridden
+        // the wrong way.
+        let link = |step : &(&str, &str)| links.iter().find(|(from, to, _)| (*from, *to) == *step);
+        assert!(steps.iter().all(|step| link(step).is_some()), "{:?}", steps);
+        for (from, to, _) in &links {
+            let two_way = links.iter().any(|(a, b, _)| (a, b) == (to, from));
+            assert!(steps.iter().any(|&(a, b)| (a, b) == (*from, *to) || (two_way && (a, b) == (*to, *from))), "{} {} in {:?}", from, to, steps);
+        }
+        let ridden : i32 = steps.iter().map(|step| match link(step) {
+            Some((_, _, Link::Length(feet))) => feet.parse::<i32>().unwrap(),
+            _ => unreachable!()
+        }).sum();
+        assert_eq!(ridden, circuit.distance);
+        assert_eq!(circuit.distance - circuit.deadhead, 4 * 100 + 2 * 140);
+    }
+
+    #[test]
+    fn refuses_a_map_that_is_not_strongly_connected() {
+        // 'x' can be left for 'b' but never reached again.
+        let mut links = both(&[("a", "b", 100), ("a", "c", 100), ("b", "c", 100)]);
+        links.push(("x", "b", length(100)));
+        let map = directed(&[("a", 0.0, 0.0), ("b", 0.0, 0.001), ("c", 0.001, 0.0), ("x", 0.001, 0.001)], &links);
+        match circuit(&map, &Turns::default()) {
+            Err(Error::Disconnected { from, to }) => assert_eq!((from.as_str(), to.as_str()), ("b", "x")),
+            _ => panic!("expected no path from b to x")
+        }
+    }
+}
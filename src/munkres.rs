@@ -1,397 +1,546 @@
 /**
  * Based of the following page: https://users.cs.duke.edu/~brd/Teaching/Bio/asmb/current/Handouts/munkres.html
+ *
+ * 'Munkres::solve' takes a zero-indexed matrix of any 'Cost' type, with forbidden cells as
+ * 'None'. 'Munkres::maximize' and 'Munkres::bottleneck' solve the same matrices for the largest
+ * total and for the smallest worst cell, and 'Munkres::best' ranks the next cheapest ones.
+ * 'Munkres::jonker_volgenant' finds the same smallest total by shortest augmenting paths, in
+ * O(n³) rather than rescanning the whole matrix for each step.
  */
-use log::{debug,info};
-
-const MAX : i32 = 1000000;
-
-const STAR : usize = 1;
-const PRIME : usize = 2;
-const NOT_FOUND : usize = 0;
-const NODE_TYPES : usize = 3;
+use log::debug;
+use serde::Serialize;
 
 use prettytable::{Table, Row, Cell};
 use std::fmt::Display;
-use ansi_term::Colour;
+use std::ops::{Add, Sub};
 use std::default::Default;
 use std::collections::HashMap;
-use std::collections::HashSet;
+
+use crate::error::{Error, Result};
 
 pub type Column<T> = Vec<T>;
 pub type Matrix<T> = Vec<Vec<T>>;
 
-struct State {
-    path : Matrix<usize>,
-    mask : Matrix<usize>,
-    array : Matrix<i32>,
-    row_cover : Column<bool>,
-    column_cover : Column<bool>
+// Solves a square matrix, returning the assigned cells.
+type Square<C> = fn(Matrix<Option<C>>) -> Vec<(usize, usize)>;
+
+// Anything that can be added up and compared: the integer and float types. The solvers keep sums
+// of up to 'size + 1' times the spread between the smallest and largest cost, where 'size' is the
+// larger side of the matrix (more with forbidden cells), and refuse matrices whose sums would not
+// fit. Small types run out quickly: a 3x3 'u8' matrix of costs from 0 to 100 needs 400, so widen
+// costs to 'i64' or 'f64' first.
+pub trait Cost : Copy + PartialOrd + Add<Output = Self> + Sub<Output = Self> + Display {
+    fn zero() -> Self;
+    fn one() -> Self;
+    // 'None' when the result does not fit (or, for floats, is not finite).
+    fn checked_add(self, other : Self) -> Option<Self>;
+    fn checked_sub(self, other : Self) -> Option<Self>;
 }
 
-impl State {
-    pub fn new(array : Matrix<i32>) -> State {
-        let size = array.len();
-        State {
-            array,
-            path: matrix(size, NODE_TYPES),
-            mask: square(size),
-            row_cover: vec![false; size],
-            column_cover: vec![false; size]
-        }
-    }
-}
+macro_rules! cost {
+    (integers : $($t : ty),*) => {
+        $(impl Cost for $t {
+            fn zero() -> $t {
+                0
+            }
 
-pub fn square<T: Default + Clone> (size : usize) -> Matrix<T> {
-    matrix(size, size)
-} 
+            fn one() -> $t {
+                1
+            }
 
-pub fn matrix<T: Default + Clone> (rows : usize, cols : usize) -> Matrix<T> {
-    vec![vec![T::default(); cols]; rows]
-} 
-
-fn row_min(array: &Matrix<i32> , row : usize) -> i32 {
-    let size : usize = array.len();
-    let mut min : i32 = MAX;
-    for i in  1..size {
-        if min > array[row][i] {
-            min = array[row][i];
-        }
-    }
-    min
-}
+            fn checked_add(self, other : $t) -> Option<$t> {
+                <$t>::checked_add(self, other)
+            }
 
-fn subtract_row(array : &mut Matrix<i32>, row : usize, num : i32) {
-    let size : usize = array.len();
-    for i in 1..size {
-        array[row][i] = array[row][i] - num;
-    }
-}
+            fn checked_sub(self, other : $t) -> Option<$t> {
+                <$t>::checked_sub(self, other)
+            }
+        })*
+    };
+    (floats : $($t : ty),*) => {
+        $(impl Cost for $t {
+            fn zero() -> $t {
+                0.0
+            }
 
-fn cover_column(column_cover : &mut Column<bool>, col : usize) {
-    column_cover[col] = true;
+            fn one() -> $t {
+                1.0
+            }
+
+            fn checked_add(self, other : $t) -> Option<$t> {
+                Some(self + other).filter(|sum| sum.is_finite())
+            }
+
+            fn checked_sub(self, other : $t) -> Option<$t> {
+                Some(self - other).filter(|difference| difference.is_finite())
+            }
+        })*
+    };
 }
 
-fn cover(state : &mut State, row : usize, col : usize) {
-    if state.array[row][col] == 0 && !state.row_cover[row] && !state.column_cover[col] {
-        state.mask[row][col] = STAR;
-        state.row_cover[row] = true;
-        state.column_cover[col] = true;
-    }
+cost!(integers : i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+cost!(floats : f32, f64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    // As many rows (or columns, if there are fewer) as there can be are assigned.
+    Solved,
+    // The forbidden cells left some of them without a partner; the pairs are the best partial
+    // assignment.
+    Stalled
 }
 
-fn find_zero(array : &mut Matrix<i32>, row_cover : &mut Column<bool>, column_cover : &mut Column<bool>) -> (usize, usize) {
-    let size = array.len();
-    for i in 1..size {
-        for j in 1..size {
-            if array[i][j] == 0 && !row_cover[i] && !column_cover[j] {
-                return (i, j);
-            }
-        }
-    }
-    (0, 0)
+pub struct Assignment<C> {
+    // (row, column) of each assigned cell, by row.
+    pub pairs : Vec<(usize, usize)>,
+    // Sum of the original costs of the assigned cells.
+    pub cost : C,
+    // Rows and columns left without a partner, because there are more of them than of the other
+    // or because of forbidden cells.
+    pub unassigned_rows : Vec<usize>,
+    pub unassigned_columns : Vec<usize>,
+    pub status : Status
 }
 
-fn get_row_mask(mask : &mut Matrix<usize>, row : usize, val : usize) -> usize {
-    let size = mask.len();
-    for i in 1..size {
-        if mask[row][i] == val {
-            return i;
-        }
+impl<C> Assignment<C> {
+    // Pairs of this assignment that 'other' does not have.
+    pub fn differs(&self, other : & Assignment<C>) -> Vec<(usize, usize)> {
+        self.pairs.iter().filter(|pair| !other.pairs.contains(pair)).copied().collect()
     }
-    NOT_FOUND
 }
 
-fn get_col_mask(mask : & Matrix<usize>, col : usize, val : usize) -> usize {
-    let size = mask.len();
-    for i in 1..size {
-        if mask[i][col] == val {
-            return i;
-        }
-    }
-    NOT_FOUND
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    Plain,
+    Star,
+    Prime
 }
 
-fn clear_covers(row_cover : &mut Column<bool>, column_cover : &mut Column<bool>) {
-    let size = row_cover.len();
-    for i in 1..size {
-        row_cover[i] = false;
-        column_cover[i] = false;
-    }
+struct State<C> {
+    // Reduced costs; 'None' is forbidden.
+    costs : Matrix<Option<C>>,
+    marks : Matrix<Mark>,
+    row_cover : Column<bool>,
+    column_cover : Column<bool>
 }
 
-fn clear_mask(mask : &mut Matrix<usize>, val : usize) {
-    let size = mask.len();
-    for i in 1..size {
-        for j in 1..size {
-            if mask[i][j] == val {
-                mask[i][j] = 0;
-            }
+impl<C : Cost> State<C> {
+    fn new(costs : Matrix<Option<C>>) -> State<C> {
+        let size = costs.len();
+        State {
+            costs,
+            marks: vec![vec![Mark::Plain; size]; size],
+            row_cover: vec![false; size],
+            column_cover: vec![false; size]
         }
     }
-}
 
-fn augment_path(path : &mut Matrix<usize>, mask : &mut Matrix<usize>, row : usize, col : usize) {
-    let size = path.len();
-    debug!("augment_path.");
-    let mut count : usize = 1;
-    path[count][STAR] = row;
-    path[count][PRIME] = col;
-    
-    loop {
-        // Find the row that contains a star'd zero using the column that contains a 
-        // prime'd zero with *no* star'd zero.
-        let row : usize = get_col_mask(mask, path[count][PRIME], STAR);
-        //println!("augment_path_row: {}", row);
-        if row > 0 {
-             count = count + 1;
-             path[count][STAR] = row;
-             path[count][PRIME] = path[count - 1][PRIME];
-        } else {
-            break
-        }
-        // Find prime in row.
-        let col : usize = get_row_mask(mask, path[count][STAR], PRIME);
-        count = count + 1;
-        path[count][STAR] = path[count - 1][STAR];
-        path[count][PRIME] = col;
+    fn zero(&self, i : usize, j : usize) -> bool {
+        self.costs[i][j].is_some_and(|cost| cost == C::zero())
     }
 
-    // Perform augmentation.
-    for i in 1..size {
-        let star : usize = path[i][STAR];
-        let prime : usize = path[i][PRIME];
-        if mask[star][prime] == STAR {
-            mask[star][prime] = PRIME;
-        } else {
-            mask[star][prime] = STAR;
-        }
+    fn clear_covers(&mut self) {
+        self.row_cover.iter_mut().for_each(|cover| *cover = false);
+        self.column_cover.iter_mut().for_each(|cover| *cover = false);
     }
-}
 
-fn solved(column_cover : &Column<bool>) -> bool {
-    let size = column_cover.len();
-    for i in 1..size {
-        if !column_cover[i] {
-            return false;
-        }
+    fn find(&self, i : usize, mark : Mark) -> Option<usize> {
+        self.marks[i].iter().position(|&m| m == mark)
     }
-    info!("solved!");
-    
-    true
-}
 
-// fn covered(row_cover : &Column<bool>, column_cover : &Column<bool>, row : usize, col : usize) -> bool {
-//     row_cover[row] & column_cover[col]
-// }
+    // First zero in neither a covered row nor a covered column.
+    fn uncovered_zero(&self) -> Option<(usize, usize)> {
+        let size = self.costs.len();
+        (0..size)
+            .filter(|&i| !self.row_cover[i])
+            .flat_map(|i| (0..size).map(move |j| (i, j)))
+            .find(|&(i, j)| !self.column_cover[j] && self.zero(i, j))
+    }
 
-fn find_min(array : &mut Matrix<i32>, row_cover : & Column<bool>, column_cover : & Column<bool>) -> i32 {
-    let size = array.len();
-    let mut min : i32 = i32::MAX;
-    for i in 1..size {
-        for j in 1..size {
-            if !column_cover[j] && !row_cover[i] {
-                min = i32::min(min, array[i][j]);
+    // Primes uncovered zeros until one has no starred zero in its row, returning it.
+    fn prime(&mut self) -> Option<(usize, usize)> {
+        while let Some((i, j)) = self.uncovered_zero() {
+            self.marks[i][j] = Mark::Prime;
+            match self.find(i, Mark::Star) {
+                Some(star) => {
+                    self.row_cover[i] = true;
+                    self.column_cover[star] = false;
+                },
+                None => return Some((i, j))
             }
         }
-    }
-    min
-}
-
-// Returns the position of an prime'd zero that does not contain a star'd zero in the
-// same row.
-fn prime(state : &mut State,
-    row : &mut usize,
-    col : &mut usize) {
-    debug!("prime.");
-
-    loop {
-        let (zero_row, zero_col) = find_zero(&mut state.array, &mut state.row_cover, &mut state.column_cover);
-        if zero_row == 0 {
-            *row = 0;
-            *col = zero_col;
-            return;
+        None
+    }
+
+    // Swaps stars and primes along the alternating path from the primed zero at (i, j), which
+    // starts one more star than it ends.
+    fn augment(&mut self, i : usize, j : usize) {
+        let mut path = vec![(i, j)];
+        let mut column = j;
+        while let Some(row) = (0..self.marks.len()).find(|&row| self.marks[row][column] == Mark::Star) {
+            path.push((row, column));
+            column = self.find(row, Mark::Prime).expect("a covered row always has a prime");
+            path.push((row, column));
         }
-        state.mask[zero_row][zero_col] = PRIME;
-
-        let star_col = get_row_mask(&mut state.mask, zero_row, STAR);
-        //println!("zero_row: {}, zero_col: {}, star_col: {}", zero_row, zero_col, star_col);
-        if star_col != NOT_FOUND {
-           state.row_cover[zero_row] = true;
-           state.column_cover[star_col] = false;
-        } else {
-            *row = zero_row;
-            *col = zero_col;
-            return;
+        for (i, j) in path {
+            self.marks[i][j] = if self.marks[i][j] == Mark::Star { Mark::Plain } else { Mark::Star };
+        }
+        for row in self.marks.iter_mut() {
+            row.iter_mut().filter(|m| **m == Mark::Prime).for_each(|m| *m = Mark::Plain);
         }
     }
-}
 
-fn augment_weights(array : &mut Matrix<i32>,
-    row_cover : & Column<bool>,
-    column_cover : & Column<bool>) {
-    debug!("augment_weights.");
-
-    let size = array.len();
-    let min : i32 = find_min(array, row_cover, column_cover);
-    if min == i32::MAX {
-        return;
-    }
-    for i in 1..size {
-        for j in 1..size {
-            if row_cover[i] {
-                array[i][j] = array[i][j] + min;
-            }
-            if !column_cover[j] {
-                array[i][j] = array[i][j] - min;
+    // Adds the smallest uncovered cost to every covered row and takes it from every uncovered
+    // column, which makes a new uncovered zero. False if there are no uncovered cells left to
+    // allow, so no more rows can be assigned.
+    fn adjust(&mut self) -> bool {
+        let size = self.costs.len();
+        let (costs, row_cover, column_cover) = (&self.costs, &self.row_cover, &self.column_cover);
+        let min = (0..size)
+            .filter(|&i| !row_cover[i])
+            .flat_map(|i| (0..size).filter(|&j| !column_cover[j]).filter_map(move |j| costs[i][j]))
+            .reduce(min);
+        let min = match min {
+            Some(min) => min,
+            None => return false
+        };
+        for i in 0..size {
+            for j in 0..size {
+                if let Some(cost) = self.costs[i][j].as_mut() {
+                    if self.row_cover[i] {
+                        *cost = *cost + min;
+                    }
+                    if !self.column_cover[j] {
+                        *cost = *cost - min;
+                    }
+                }
             }
         }
+        true
     }
 }
 
-pub fn copy_matrix(array : & Matrix<i32>) -> Matrix<i32> {
+// Assignment problems: one cell in each row and column, for the smallest total cost.
+pub struct Munkres;
 
-    let mut matrix = square(array.len());
-    for i in 1..matrix.len() {
-        for j in 1..matrix.len() {
-            matrix[i][j] = array[i][j];
-            //info!("{}.{} => {}", i, j, matrix[i][j]);
-        }
+impl Munkres {
+    // 'costs' has a row per row and a column per column, and need not be square; 'None' cells
+    // are forbidden and never assigned. As many rows (or columns, if there are fewer) are
+    // assigned as the forbidden cells allow, as cheaply as possible.
+    pub fn solve<C : Cost>(costs : & [Vec<Option<C>>]) -> Result<Assignment<C>> {
+        Munkres::assign(costs, Munkres::stars)
     }
 
-    matrix
-}
-
-pub fn solve(array : Matrix<i32>, keys : HashMap<usize, (String, usize)>) {
+    // As 'solve', by shortest augmenting paths over row and column potentials (Jonker-Volgenant)
+    // rather than the steps: O(n³), for matrices of thousands of rows.
+    pub fn jonker_volgenant<C : Cost>(costs : & [Vec<Option<C>>]) -> Result<Assignment<C>> {
+        Munkres::assign(costs, Munkres::potentials)
+    }
 
-    debug!("printing input weight matrix (pre-processed)...");
-    print_raw_matrix(&array);
+    // Pads the costs to a square for 'square_solve', which returns the assigned cells and may stop
+    // short of a complete assignment if the forbidden cells do not allow one. Ragged matrices, and
+    // costs too far apart for the sums either algorithm works out, are refused rather than
+    // panicking or overflowing.
+    fn assign<C : Cost>(costs : & [Vec<Option<C>>], square_solve : Square<C>) -> Result<Assignment<C>> {
+        let rows = costs.len();
+        let columns = costs.first().map_or(0, |row| row.len());
+        if costs.iter().any(|row| row.len() != columns) {
+            return Err(Error::Matrix(format!("expected {} columns in every row, like the first", columns)));
+        }
 
-    let size = array.len();
-    let original = copy_matrix(&array);
-    let mut state : State = State::new(array);
-    // Keep previous state to ensure progress and help debuggability.
-    let mut copy = State::new(square(size));
-   
-    debug!("printing state.weights matrix (pre-processed)...");
-    print_raw_matrix(&state.array);
-    // 1.
-    for i in 1..size {
-        let min = row_min(&state.array, i);
-        subtract_row(&mut state.array, i, min);
-    }
-    // 2.
-    for i in 1..size {
-        for j in 1..size {
-            cover(&mut state, i, j);
+        // Pad to a square with free rows or columns; whatever is assigned one is left unassigned.
+        let size = rows.max(columns);
+        let padded : Matrix<Option<C>> = (0..size)
+            .map(|i| (0..size).map(|j| if i < rows && j < columns { costs[i][j] } else { Some(C::zero()) }).collect())
+            .collect();
+        let allowed : Vec<C> = padded.iter().flatten().flatten().copied().collect();
+        let low = allowed.iter().copied().reduce(min).unwrap_or(C::zero());
+        let high = allowed.iter().copied().reduce(max).unwrap_or(C::zero());
+        headroom(low, high, size)?;
+        let mut pairs = square_solve(padded.clone());
+        if pairs.len() < size {
+            // Allow the forbidden cells at a cost no saving elsewhere can make up for, so the
+            // fewest possible are used, then leave them out.
+            let spread = high.checked_sub(low).ok_or_else(overflow)?;
+            let penalty = (0..size)
+                .try_fold(high.checked_add(C::one()).ok_or_else(overflow)?, |penalty, _| penalty.checked_add(spread))
+                .ok_or_else(overflow)?;
+            headroom(low, penalty, size)?;
+            let filled = padded.iter().map(|row| row.iter().map(|cost| Some(cost.unwrap_or(penalty))).collect()).collect();
+            pairs = square_solve(filled);
         }
-    }
-    clear_covers(&mut state.row_cover, &mut state.column_cover);
-    debug!("printing state.weights matrix (post-processed)...");
-    print_raw_matrix(&state.array);
-
-    // Decides whether the next iteration of the loop is to start at step #3 or step #4.
-    let mut offset = false;
-    loop {
-        // 3.
-        if !offset {
-            for i in 1..size {
-                for j in 1..size {
-                    if state.mask[i][j] == STAR {
-                        cover_column(&mut state.column_cover, j);
+        pairs.retain(|&(i, j)| i < rows && j < columns && costs[i][j].is_some());
+        let status = if pairs.len() < rows.min(columns) {
+            debug!("forbidden cells leave {} of {} rows unassigned.", rows.min(columns) - pairs.len(), rows.min(columns));
+            Status::Stalled
+        } else {
+            Status::Solved
+        };
+
+        let cost = total(costs, &pairs)?;
+        let unassigned_rows = (0..rows).filter(|&i| !pairs.iter().any(|&(row, _)| row == i)).collect();
+        let unassigned_columns = (0..columns).filter(|&j| !pairs.iter().any(|&(_, column)| column == j)).collect();
+        Ok(Assignment { pairs, cost, unassigned_rows, unassigned_columns, status })
+    }
+
+    // As 'solve', for the largest total instead, e.g. of preference scores.
+    pub fn maximize<C : Cost>(costs : & [Vec<Option<C>>]) -> Result<Assignment<C>> {
+        let high = match costs.iter().flatten().flatten().copied().reduce(max) {
+            Some(high) => high,
+            None => return Munkres::solve(costs)
+        };
+        // Measured down from the largest cost, so unsigned costs stay positive.
+        let flipped : Matrix<Option<C>> = costs
+            .iter()
+            .map(|row| row.iter().map(|cost| cost.map(|cost| high.checked_sub(cost).ok_or_else(overflow)).transpose()).collect())
+            .collect::<Result<_>>()?;
+        let assignment = Munkres::solve(&flipped)?;
+        Ok(Assignment { cost: total(costs, &assignment.pairs)?, ..assignment })
+    }
+
+    // As 'solve', for the smallest worst (largest) assigned cost instead: no row gets a
+    // terrible cell if it can be helped. Among the assignments with that worst cost, the total
+    // is as small as possible.
+    pub fn bottleneck<C : Cost>(costs : & [Vec<Option<C>>]) -> Result<Assignment<C>> {
+        let mut thresholds : Vec<C> = costs.iter().flatten().flatten().copied().collect();
+        thresholds.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        thresholds.dedup();
+        if thresholds.is_empty() {
+            return Munkres::solve(costs);
+        }
+        let within = |threshold : C| -> Matrix<Option<C>> {
+            costs
+                .iter()
+                .map(|row| row.iter().map(|cost| cost.filter(|&cost| cost <= threshold)).collect())
+                .collect()
+        };
+        let assigned = Munkres::solve(costs)?.pairs.len();
+
+        // The smallest threshold that still assigns as many rows as everything does.
+        let (mut low, mut high) = (0, thresholds.len() - 1);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if Munkres::solve(&within(thresholds[middle]))?.pairs.len() == assigned {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+        Munkres::solve(&within(thresholds[high]))
+    }
+
+    // The 'count' cheapest assignments, cheapest first, each assigning as many rows as 'solve'
+    // (Murty's ranking). Every assignment still to be ranked either leaves out one of the pairs
+    // of the last one ranked or keeps them all, so the matrix it came from is split into one
+    // smaller problem per pair: that pair forbidden and the pairs before it forced.
+    pub fn best<C : Cost>(costs : & [Vec<Option<C>>], count : usize) -> Result<Vec<Assignment<C>>> {
+        let first = Munkres::solve(costs)?;
+        let assigned = first.pairs.len();
+        let mut problems : Vec<(Matrix<Option<C>>, Assignment<C>)> = vec![(costs.to_vec(), first)];
+        let mut ranked : Vec<Assignment<C>> = Vec::new();
+        while ranked.len() < count && !problems.is_empty() {
+            let next = (1..problems.len()).fold(0, |best, k| if problems[k].1.cost < problems[best].1.cost { k } else { best });
+            let (mut forced, assignment) = problems.remove(next);
+            for &(i, j) in &assignment.pairs {
+                let mut excluded = forced.clone();
+                excluded[i][j] = None;
+                let alternative = Munkres::solve(&excluded)?;
+                // Fewer rows assigned is not an alternative, just a worse problem.
+                if alternative.pairs.len() == assigned {
+                    problems.push((excluded, alternative));
+                }
+                // Nothing else in its row or column, so a full assignment has to use it.
+                for (row, cells) in forced.iter_mut().enumerate() {
+                    for (column, cell) in cells.iter_mut().enumerate() {
+                        if (row == i) != (column == j) {
+                            *cell = None;
+                        }
                     }
                 }
             }
-            if solved(&state.column_cover) {
-                info!("solved in stage 3 ...");
-                validate(&state.mask, &original, &keys);
-                print_matrix(state.mask, keys);
-                return
-            }
-            offset = true
-        }
-        let (mut row, mut col) = (0, 0);
-        // 4.
-        if offset {
-            prime(&mut state, &mut row, &mut col);
+            ranked.push(assignment);
         }
-        // 5.
-        if row > 0 && col > 0 {
-            // A path starting with a prime'd zero was found, so try to augment the PATH to increase the
-            // size of the matching.
-            augment_path(&mut state.path, &mut state.mask, row, col);
-            clear_covers(&mut state.row_cover, &mut state.column_cover);
-            clear_mask(&mut state.mask, PRIME);
-            offset = false;
-        } else {
-            // 6.
-            augment_weights(&mut state.array, &state.row_cover, &state.column_cover);
+        Ok(ranked)
+    }
+
+    // Adds the rows one at a time, each along the shortest path of reduced costs (Dijkstra's)
+    // from the row to a free column, flipping the pairs along it. The potentials keep reduced
+    // costs from going negative, and each row takes O(n²). Stops short of a complete assignment
+    // if a row cannot reach a free column.
+    fn potentials<C : Cost>(mut costs : Matrix<Option<C>>) -> Vec<(usize, usize)> {
+        let size = costs.len();
+        // As step 1, so the costs (and so the potentials) never go below zero, even unsigned.
+        for row in costs.iter_mut() {
+            if let Some(min) = row.iter().flatten().copied().reduce(min) {
+                row.iter_mut().flatten().for_each(|cost| *cost = *cost - min);
+            }
         }
 
-        if solved(&state.column_cover) {
-            info!("solved in stage 6 ...");
-            validate(&state.mask, &original, &keys);
-            print_matrix(state.mask, keys);
-            return;
+        // Column 'size' stands for the row being added. Reduced costs are
+        // 'cost + column[j] - row[i]'.
+        let mut row : Column<C> = vec![C::zero(); size];
+        let mut column : Column<C> = vec![C::zero(); size + 1];
+        let mut owner : Column<Option<usize>> = vec![None; size + 1];
+        let mut way : Column<usize> = vec![size; size + 1];
+        for i in 0..size {
+            owner[size] = Some(i);
+            let mut shortest : Column<Option<C>> = vec![None; size + 1];
+            let mut used : Column<bool> = vec![false; size + 1];
+            let mut current = size;
+            loop {
+                used[current] = true;
+                let from = owner[current].unwrap();
+                let mut closest : Option<(C, usize)> = None;
+                for j in 0..size {
+                    if used[j] {
+                        continue;
+                    }
+                    if let Some(cost) = costs[from][j] {
+                        let reduced = cost + column[j] - row[from];
+                        if shortest[j].is_none_or(|distance| reduced < distance) {
+                            shortest[j] = Some(reduced);
+                            way[j] = current;
+                        }
+                    }
+                    if let Some(distance) = shortest[j] {
+                        if closest.is_none_or(|(delta, _)| distance < delta) {
+                            closest = Some((distance, j));
+                        }
+                    }
+                }
+                let (delta, next) = match closest {
+                    Some(closest) => closest,
+                    None => {
+                        debug!("row {} cannot reach a free column ...", i);
+                        return Munkres::owned(&owner);
+                    }
+                };
+                for j in 0..=size {
+                    if used[j] {
+                        row[owner[j].unwrap()] = row[owner[j].unwrap()] + delta;
+                        column[j] = column[j] + delta;
+                    } else if let Some(distance) = shortest[j] {
+                        shortest[j] = Some(distance - delta);
+                    }
+                }
+                current = next;
+                if owner[current].is_none() {
+                    break;
+                }
+            }
+            // Flip the pairs back along the path.
+            while current != size {
+                let previous = way[current];
+                owner[current] = owner[previous];
+                current = previous;
+            }
         }
+        Munkres::owned(&owner)
+    }
 
-        if !makes_progress(&state, &mut copy) {
-            info!("col_covered_count: {}, row_covered_count: {}", col_covered_count(&mut state.column_cover), row_covered_count(&mut state.column_cover));
-            return;
-        }
+    // The (row, column) cells of the columns with an owner, by row.
+    fn owned(owner : & [Option<usize>]) -> Vec<(usize, usize)> {
+        let size = owner.len() - 1;
+        let mut pairs : Vec<(usize, usize)> = (0..size).filter_map(|j| owner[j].map(|i| (i, j))).collect();
+        pairs.sort();
+        pairs
     }
 
-}
+    // Runs the steps over the costs, returning the starred cells. Stops short of a complete
+    // assignment if the forbidden cells do not allow one.
+    fn stars<C : Cost>(costs : Matrix<Option<C>>) -> Vec<(usize, usize)> {
+        let size = costs.len();
+        let mut state = State::new(costs);
 
-fn validate(mask : & Matrix<usize>, cost : & Matrix<i32>, keys : & HashMap<usize, (String, usize)>) {
-    let row_cover : Column<bool> = vec![false; mask.len()];
-    let column_cover : Column<bool> = vec![false; mask.len()];
-   
-    let mut nodes : HashSet<(usize, usize)> = HashSet::new();
-    for i in 1..mask.len() {
-        for j in 1..mask.len() {
-            if mask[i][j] == 1 {
-                if row_cover[i] || column_cover[j] {
-                    info!("found conflict @ ({},{}) ...", i, j);
-                } else {
-                    nodes.insert((usize::min(i, j), usize::max(i, j)));
+        // 1.
+        for row in state.costs.iter_mut() {
+            if let Some(min) = row.iter().flatten().copied().reduce(min) {
+                row.iter_mut().flatten().for_each(|cost| *cost = *cost - min);
+            }
+        }
+        // 2.
+        for i in 0..size {
+            for j in 0..size {
+                if state.zero(i, j) && !state.row_cover[i] && !state.column_cover[j] {
+                    state.marks[i][j] = Mark::Star;
+                    state.row_cover[i] = true;
+                    state.column_cover[j] = true;
                 }
             }
         }
-    }
+        state.clear_covers();
 
-    print_raw_matrix(cost);
+        'solve : loop {
+            // 3.
+            for j in 0..size {
+                state.column_cover[j] = (0..size).any(|i| state.marks[i][j] == Mark::Star);
+            }
+            if state.column_cover.iter().all(|&cover| cover) {
+                debug!("solved in stage 3 ...");
+                break;
+            }
+            loop {
+                // 4.
+                match state.prime() {
+                    // 5.
+                    Some((i, j)) => {
+                        state.augment(i, j);
+                        state.clear_covers();
+                        break;
+                    },
+                    // 6.
+                    None => if !state.adjust() {
+                        break 'solve;
+                    }
+                }
+            }
+        }
 
-    let mut total_cost = 0;
-    for node in nodes {
-        let (i, j) = node;
-        let (from_name, _) = keys.get(&i).unwrap();
-        let (to_name, _) = keys.get(&j).unwrap();
-        info!("{} => {} ({})", Colour::Green.paint(from_name), Colour::Red.paint(to_name), cost[i][j]);
-        total_cost = total_cost + cost[i][j];
+        (0..size)
+            .filter_map(|i| state.find(i, Mark::Star).map(|j| (i, j)))
+            .collect()
     }
+}
 
-    info!("total cost = {}", total_cost);
+// Sum of the costs of the assigned cells.
+fn total<C : Cost>(costs : & [Vec<Option<C>>], pairs : & [(usize, usize)]) -> Result<C> {
+    pairs
+        .iter()
+        .filter_map(|&(i, j)| costs[i][j])
+        .try_fold(C::zero(), |total, cost| total.checked_add(cost))
+        .ok_or_else(overflow)
+}
 
+fn overflow() -> Error {
+    Error::Matrix("the costs are too large to add up".to_string())
 }
 
-fn col_covered_count(column_cover : &mut Column<bool>) -> i32 {
-    let mut count : i32 = 0;
-    for i in 1..column_cover.len() {
-        if column_cover[i] {
-            count = count + 1;
-        }
-    }
-    count
+// Once rows are reduced every cost lies between zero and the spread of the costs, and the sums
+// either algorithm keeps (potentials, adjusted costs) stay within 'size + 1' spreads of zero.
+fn headroom<C : Cost>(low : C, high : C, size : usize) -> Result<()> {
+    let spread = high.checked_sub(low).ok_or_else(overflow)?;
+    (0..=size)
+        .try_fold(C::zero(), |sum, _| sum.checked_add(spread))
+        .map(|_| ())
+        .ok_or_else(overflow)
 }
 
-fn row_covered_count(row_cover : &mut Column<bool>) -> i32 {
-    let mut count : i32 = 0;
-    for i in 1..row_cover.len() {
-        if row_cover[i] {
-            count = count + 1;
-        }
-    }
-    count
+// The smaller of two costs ('PartialOrd' has no 'min').
+fn min<C : Cost>(a : C, b : C) -> C {
+    if b < a { b } else { a }
+}
+
+fn max<C : Cost>(a : C, b : C) -> C {
+    if b > a { b } else { a }
+}
+
+pub fn square<T: Default + Clone> (size : usize) -> Matrix<T> {
+    matrix(size, size)
+}
+
+pub fn matrix<T: Default + Clone> (rows : usize, cols : usize) -> Matrix<T> {
+    vec![vec![T::default(); cols]; rows]
 }
 
 pub fn print_raw_matrix<T : Display> (matrix : & Matrix<T>) {
@@ -403,21 +552,21 @@ pub fn print_raw_matrix<T : Display> (matrix : & Matrix<T>) {
         header.push(Cell::new(&i.to_string()));
     }
     table.add_row(Row::new(header));
-   
-    for i in 1..size {
+
+    for (i, values) in matrix.iter().enumerate().skip(1) {
         let mut row : Vec<Cell> = Vec::new();
-        row.push(Cell::new(&format!("{}", &i.to_string())));
-        for j in 1..size {
-            row.push(Cell::new(&matrix[i][j].to_string()));
+        row.push(Cell::new(&i.to_string()));
+        for value in values.iter().skip(1) {
+            row.push(Cell::new(&value.to_string()));
         }
         table.add_row(Row::new(row));
     }
-    table.printstd();
+    debug!("\n{}", table);
 }
 
-fn print_matrix<T : Display> (matrix : Matrix<T>, keys : HashMap<usize, (String, usize)>, ) {
+pub fn print_matrix<T : Display> (matrix : Matrix<T>, keys : HashMap<usize, (String, usize)>, ) {
     let mut table = Table::new();
-    
+
     let mut header : Vec<Cell> = vec![Cell::new(" "), Cell::new(" ")];
 
     let size = matrix.len();
@@ -427,67 +576,208 @@ fn print_matrix<T : Display> (matrix : Matrix<T>, keys : HashMap<usize, (String,
     }
     table.add_row(Row::new(header));
 
-    for i in 1..size {
+    for (i, values) in matrix.iter().enumerate().skip(1) {
         let mut row : Vec<Cell> = Vec::new();
         match keys.get(&i) {
             Some((name, _)) => {
-                row.push(Cell::new(&name).style_spec("bFr"));
+                row.push(Cell::new(name).style_spec("bFr"));
             },
             None => {
-                panic!("Not found: {}", &i);
+                row.push(Cell::new(" "));
             }
         }
-        row.push(Cell::new(&format!("{}", &i.to_string())));
-        for j in 1..size {
-            row.push(Cell::new(&matrix[i][j].to_string()));
+        row.push(Cell::new(&i.to_string()));
+        for value in values.iter().skip(1) {
+            row.push(Cell::new(&value.to_string()));
         }
         table.add_row(Row::new(row));
     }
-    table.printstd();
+    debug!("\n{}", table);
 }
 
-fn makes_progress(state : & State, old : &mut State) -> bool {
-   
-    let size : usize = state.array.len();
-    let mut progress : bool = false;
-    for i in 1..size {
-        for j in 1..size {
-            if old.array[i][j] != state.array[i][j] {
-                progress = true;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::Random;
+
+    // A small pseudo-random matrix, the same on every run.
+    fn sample(random : &mut Random, rows : usize, columns : usize, forbidden : u64) -> Matrix<Option<i64>> {
+        (0..rows)
+            .map(|_| (0..columns).map(|_| {
+                let cost = random.next(30) as i64 - 5;
+                if random.next(100) < forbidden { None } else { Some(cost) }
+            }).collect())
+            .collect()
+    }
+
+    // Every assignment of as many rows as the forbidden cells allow.
+    fn every(costs : & [Vec<Option<i64>>]) -> Vec<Vec<(usize, usize)>> {
+        fn extend(costs : & [Vec<Option<i64>>], row : usize, used : &mut Vec<bool>, pairs : &mut Vec<(usize, usize)>, found : &mut Vec<Vec<(usize, usize)>>) {
+            if row == costs.len() {
+                found.push(pairs.clone());
+                return;
+            }
+            extend(costs, row + 1, used, pairs, found);
+            for j in 0..used.len() {
+                if !used[j] && costs[row][j].is_some() {
+                    used[j] = true;
+                    pairs.push((row, j));
+                    extend(costs, row + 1, used, pairs, found);
+                    pairs.pop();
+                    used[j] = false;
+                }
             }
-            old.array[i][j] = state.array[i][j];
         }
+        let columns = costs.first().map_or(0, |row| row.len());
+        let mut found = Vec::new();
+        extend(costs, 0, &mut vec![false; columns], &mut Vec::new(), &mut found);
+        let most = found.iter().map(|pairs| pairs.len()).max().unwrap_or(0);
+        found.retain(|pairs| pairs.len() == most);
+        found
+    }
+
+    fn cells(costs : & [Vec<Option<i64>>], pairs : & [(usize, usize)]) -> Vec<i64> {
+        pairs.iter().map(|&(i, j)| costs[i][j].unwrap()).collect()
+    }
+
+    // A valid assignment of as many rows as possible, with its lists and total right.
+    fn check(costs : & [Vec<Option<i64>>], assignment : & Assignment<i64>) {
+        let columns = costs.first().map_or(0, |row| row.len());
+        let most = every(costs)[0].len();
+        assert_eq!(assignment.pairs.len(), most, "{:?}", costs);
+        let mut rows = vec![false; costs.len()];
+        let mut taken = vec![false; columns];
+        for &(i, j) in &assignment.pairs {
+            assert!(costs[i][j].is_some() && !rows[i] && !taken[j], "{:?}", costs);
+            rows[i] = true;
+            taken[j] = true;
+        }
+        assert_eq!(assignment.cost, cells(costs, &assignment.pairs).iter().sum::<i64>());
+        assert_eq!(assignment.unassigned_rows, (0..costs.len()).filter(|&i| !rows[i]).collect::<Vec<_>>());
+        assert_eq!(assignment.unassigned_columns, (0..columns).filter(|&j| !taken[j]).collect::<Vec<_>>());
+        let status = if most < costs.len().min(columns) { Status::Stalled } else { Status::Solved };
+        assert_eq!(assignment.status, status);
     }
-    for i in 1..size {
-        for j in 1..size {
-            if old.mask[i][j] != state.mask[i][j] {
-                progress = true;
+
+    fn cheapest(costs : & [Vec<Option<i64>>]) -> i64 {
+        every(costs).iter().map(|pairs| cells(costs, pairs).iter().sum()).min().unwrap()
+    }
+
+    // Square, wide and tall matrices, with and without forbidden cells.
+    fn shapes() -> Vec<Matrix<Option<i64>>> {
+        let mut random = Random(7);
+        let mut shapes = Vec::new();
+        for &(rows, columns) in &[(1, 1), (3, 3), (5, 5), (2, 5), (3, 6), (5, 2), (6, 4)] {
+            for &forbidden in &[0, 20, 50] {
+                for _ in 0..4 {
+                    shapes.push(sample(&mut random, rows, columns, forbidden));
+                }
             }
-            old.mask[i][j] = state.mask[i][j];
         }
+        shapes
     }
 
-    for i in 1..size {
-        if old.column_cover[i] != state.column_cover[i] {
-            progress = true;
+    #[test]
+    fn solve_matches_brute_force() {
+        for costs in shapes() {
+            let assignment = Munkres::solve(&costs).unwrap();
+            check(&costs, &assignment);
+            assert_eq!(assignment.cost, cheapest(&costs), "{:?}", costs);
         }
-        old.column_cover[i] = state.column_cover[i];
     }
-    for i in 1..size {
-        if old.row_cover[i] != state.row_cover[i] {
-            progress = true;
+
+    #[test]
+    fn jonker_volgenant_matches_brute_force() {
+        for costs in shapes() {
+            let assignment = Munkres::jonker_volgenant(&costs).unwrap();
+            check(&costs, &assignment);
+            assert_eq!(assignment.cost, cheapest(&costs), "{:?}", costs);
+        }
+        // Floats and unsigned costs as well.
+        let costs = vec![vec![Some(2.5), Some(0.25)], vec![Some(1.0), None]];
+        assert_eq!(Munkres::jonker_volgenant(&costs).unwrap().cost, 1.25);
+        let costs : Matrix<Option<u32>> = vec![vec![Some(200), Some(3), Some(90)], vec![Some(7), Some(250), Some(0)]];
+        assert_eq!(Munkres::jonker_volgenant(&costs).unwrap().cost, 3);
+    }
+
+    #[test]
+    fn solve_without_allowed_cells() {
+        let costs : Matrix<Option<i64>> = vec![vec![None, None], vec![None, None]];
+        let assignment = Munkres::solve(&costs).unwrap();
+        assert!(assignment.pairs.is_empty());
+        assert_eq!(assignment.unassigned_rows, vec![0, 1]);
+        assert_eq!(assignment.unassigned_columns, vec![0, 1]);
+    }
+
+    #[test]
+    fn maximize_matches_brute_force() {
+        for costs in shapes() {
+            let assignment = Munkres::maximize(&costs).unwrap();
+            check(&costs, &assignment);
+            let largest : i64 = every(&costs).iter().map(|pairs| cells(&costs, pairs).iter().sum()).max().unwrap();
+            assert_eq!(assignment.cost, largest, "{:?}", costs);
         }
-        old.row_cover[i] = state.row_cover[i];
     }
 
-    for i in 1..size {
-        for j in 1..NODE_TYPES {
-            if old.path[i][j] != state.path[i][j] {
-                progress = true;
+    #[test]
+    fn bottleneck_minimizes_the_worst_cell() {
+        // Worst cell first, then the total.
+        let key = |costs : &Matrix<Option<i64>>, pairs : &[(usize, usize)]| {
+            let cells = cells(costs, pairs);
+            (cells.iter().copied().max(), cells.iter().sum::<i64>())
+        };
+        for costs in shapes() {
+            let assignment = Munkres::bottleneck(&costs).unwrap();
+            check(&costs, &assignment);
+            let best = every(&costs).iter().map(|pairs| key(&costs, pairs)).min().unwrap();
+            assert_eq!(key(&costs, &assignment.pairs), best, "{:?}", costs);
+        }
+        // The cheapest total gives row 1 a cost of 10; the bottleneck spreads it out.
+        let costs = vec![vec![Some(0), Some(6)], vec![Some(6), Some(10)]];
+        assert_eq!(Munkres::solve(&costs).unwrap().pairs, vec![(0, 0), (1, 1)]);
+        assert_eq!(Munkres::bottleneck(&costs).unwrap().pairs, vec![(0, 1), (1, 0)]);
+    }
+
+    #[test]
+    fn best_ranks_in_ascending_cost() {
+        for costs in shapes() {
+            let mut totals : Vec<i64> = every(&costs).iter().map(|pairs| cells(&costs, pairs).iter().sum()).collect();
+            totals.sort();
+            let ranked = Munkres::best(&costs, 6).unwrap();
+            assert_eq!(ranked.len(), totals.len().min(6), "{:?}", costs);
+            for (assignment, &total) in ranked.iter().zip(&totals) {
+                check(&costs, assignment);
+                assert_eq!(assignment.cost, total, "{:?}", costs);
             }
-            old.path[i][j] = state.path[i][j];
+            // Each one is a different assignment, and the first is what 'solve' finds.
+            for (k, assignment) in ranked.iter().enumerate().skip(1) {
+                assert!(!assignment.differs(&ranked[0]).is_empty());
+                assert!(ranked[..k].iter().all(|earlier| earlier.pairs != assignment.pairs));
+            }
+            assert_eq!(ranked[0].cost, Munkres::solve(&costs).unwrap().cost);
         }
     }
 
-    return progress;
-}
\ No newline at end of file
+    #[test]
+    fn solve_refuses_overflow() {
+        let costs = vec![vec![Some(4000000000000000000i64), None], vec![Some(1), None]];
+        assert!(matches!(Munkres::solve(&costs), Err(Error::Matrix(_))));
+    }
+
+    #[test]
+    fn solve_refuses_ragged_matrices() {
+        let costs = vec![vec![Some(1), Some(2)], vec![Some(3)]];
+        assert!(matches!(Munkres::solve(&costs), Err(Error::Matrix(_))));
+        assert!(matches!(Munkres::best(&costs, 2), Err(Error::Matrix(_))));
+    }
+
+    #[test]
+    fn small_types_need_room_for_the_sums() {
+        // Needs 4 spreads of 100, more than a 'u8' holds; the same costs widened solve.
+        let costs : Matrix<Option<u8>> = vec![vec![Some(0), Some(100), Some(50)], vec![Some(100), Some(0), Some(50)], vec![Some(50), Some(50), Some(0)]];
+        assert!(matches!(Munkres::solve(&costs), Err(Error::Matrix(_))));
+        let wide : Matrix<Option<i64>> = costs.iter().map(|row| row.iter().map(|cost| cost.map(i64::from)).collect()).collect();
+        assert_eq!(Munkres::solve(&wide).unwrap().cost, 0);
+    }
+}
@@ -0,0 +1,314 @@
+/**
+ * Imports intersections from an OpenStreetMap XML extract (e.g. exported from openstreetmap.org
+ * or cut with osmium). Cyclable ways are split wherever they meet another way, and each piece
+ * becomes a street whose length is measured along the way's geometry.
+ */
+use log::{debug,info};
+
+use std::collections::HashMap;
+
+use crate::Intersection;
+use crate::attributes::{Attributes, Link};
+use crate::geo::haversine;
+
+const CYCLABLE : [&str; 17] = [
+    "primary", "primary_link", "secondary", "secondary_link", "tertiary", "tertiary_link",
+    "unclassified", "residential", "living_street", "service", "road", "cycleway", "path",
+    "track", "footway", "pedestrian", "bridleway"
+];
+
+// Ways where bikes are only allowed when explicitly tagged.
+const BICYCLE_REQUIRED : [&str; 3] = ["footway", "pedestrian", "bridleway"];
+
+struct Point {
+    latitude : f64,
+    longitude : f64,
+    altitude : f64
+}
+
+struct Way {
+    name : Option<String>,
+    nodes : Vec<i64>,
+    attributes : Attributes,
+    // oneway=-1: the way may only be ridden against the order of its nodes.
+    reversed : bool
+}
+
+fn cyclable(tags : & HashMap<&str, &str>) -> bool {
+    let highway = match tags.get("highway") {
+        Some(highway) => *highway,
+        None => return false
+    };
+    let bicycle = tags.get("bicycle").copied().unwrap_or("");
+    let access = tags.get("access").copied().unwrap_or("");
+
+    if !CYCLABLE.contains(&highway) || bicycle == "no" || bicycle == "dismount" {
+        return false;
+    }
+    if (access == "no" || access == "private") && bicycle != "yes" && bicycle != "designated" {
+        return false;
+    }
+    if tags.get("area") == Some(&"yes") {
+        return false;
+    }
+    !BICYCLE_REQUIRED.contains(&highway) || bicycle == "yes" || bicycle == "designated"
+}
+
+// Street attributes carried over onto every link of the way.
+fn attributes(tags : & HashMap<&str, &str>) -> Attributes {
+    let lanes : Vec<&str> = ["cycleway", "cycleway:left", "cycleway:right", "cycleway:both"]
+        .iter()
+        .filter_map(|key| tags.get(key).copied())
+        .collect();
+    let bike_lane = if lanes.iter().any(|lane| ["lane", "track", "opposite_lane", "opposite_track"].contains(lane)) {
+        Some(true)
+    } else if lanes.contains(&"no") {
+        Some(false)
+    } else {
+        None
+    };
+    let oneway = matches!(tags.get("oneway"), Some(&"yes") | Some(&"1") | Some(&"-1"))
+        && tags.get("oneway:bicycle") != Some(&"no");
+    Attributes {
+        street: tags.get("name").map(|name| name.to_string()),
+        class: tags.get("highway").map(|highway| highway.to_string()),
+        surface: tags.get("surface").map(|surface| surface.to_string()),
+        bike_lane,
+        oneway
+    }
+}
+
+fn slug(text : &str) -> String {
+    text.to_lowercase()
+        .split(|c : char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// Names intersections the same way as the hand built maps: '30th-ave-sw&sw-nevada-st', with
+// the OSM id appended when the streets alone are not unique.
+fn names(junctions : & [i64], streets : & HashMap<i64, Vec<String>>) -> HashMap<i64, (String, String)> {
+    let mut names : HashMap<i64, (String, String)> = HashMap::new();
+    let mut used : HashMap<String, usize> = HashMap::new();
+
+    let mut candidates : Vec<(i64, String, String)> = Vec::new();
+    for id in junctions {
+        let mut streets = streets.get(id).cloned().unwrap_or_default();
+        streets.sort();
+        streets.dedup();
+        let name = streets.iter().map(|street| slug(street)).collect::<Vec<_>>().join("&");
+        let address = streets.join(" & ");
+        *used.entry(name.clone()).or_insert(0) += 1;
+        candidates.push((*id, name, address));
+    }
+
+    for (id, name, address) in candidates {
+        let name = if name.is_empty() {
+            format!("node-{}", id)
+        } else if used[&name] > 1 {
+            format!("{}-{}", name, id)
+        } else {
+            name
+        };
+        names.insert(id, (name, address));
+    }
+    names
+}
+
+// Parses an .osm extract into the intersections 'build_map' consumes.
+pub(crate) fn read(contents : &str) -> Result<Vec<Intersection>, roxmltree::Error> {
+    let document = roxmltree::Document::parse(contents)?;
+
+    let mut points : HashMap<i64, Point> = HashMap::new();
+    let mut ways : Vec<Way> = Vec::new();
+    for element in document.root_element().children().filter(|n| n.is_element()) {
+        let tags : HashMap<&str, &str> = element
+            .children()
+            .filter(|n| n.has_tag_name("tag"))
+            .filter_map(|n| Some((n.attribute("k")?, n.attribute("v")?)))
+            .collect();
+        match element.tag_name().name() {
+            "node" => {
+                let id = element.attribute("id").and_then(|id| id.parse().ok());
+                let latitude = element.attribute("lat").and_then(|lat| lat.parse().ok());
+                let longitude = element.attribute("lon").and_then(|lon| lon.parse().ok());
+                if let (Some(id), Some(latitude), Some(longitude)) = (id, latitude, longitude) {
+                    let altitude = tags.get("ele").and_then(|ele| ele.parse().ok()).unwrap_or(0.0);
+                    points.insert(id, Point { latitude, longitude, altitude });
+                }
+            },
+            "way" if cyclable(&tags) => {
+                ways.push(Way {
+                    name: tags.get("name").map(|name| name.to_string()),
+                    nodes: element
+                        .children()
+                        .filter(|n| n.has_tag_name("nd"))
+                        .filter_map(|n| n.attribute("ref")?.parse().ok())
+                        .collect(),
+                    attributes: attributes(&tags),
+                    reversed: tags.get("oneway") == Some(&"-1")
+                });
+            },
+            _ => ()
+        }
+    }
+    info!("found {} nodes and {} cyclable ways.", points.len(), ways.len());
+
+    // Drop references to nodes outside of the extract.
+    for way in ways.iter_mut() {
+        way.nodes.retain(|id| points.contains_key(id));
+    }
+    ways.retain(|way| way.nodes.len() > 1);
+
+    // A node is a junction if it ends a way or is shared by more than one way.
+    let mut uses : HashMap<i64, usize> = HashMap::new();
+    let mut streets : HashMap<i64, Vec<String>> = HashMap::new();
+    for way in &ways {
+        for (pos, id) in way.nodes.iter().enumerate() {
+            let end = pos == 0 || pos == way.nodes.len() - 1;
+            *uses.entry(*id).or_insert(0) += if end { 2 } else { 1 };
+            if let Some(name) = &way.name {
+                streets.entry(*id).or_default().push(name.clone());
+            }
+        }
+    }
+    let mut junctions : Vec<i64> = uses.iter().filter(|(_, &count)| count > 1).map(|(id, _)| *id).collect();
+    junctions.sort_unstable();
+    let names = names(&junctions, &streets);
+
+    // Split each way at its junctions, keeping the shortest street between two junctions. Both
+    // directions are always listed; only the legal one of a one-way street is flagged 'oneway'.
+    let mut neighbours : HashMap<i64, HashMap<i64, (f64, Attributes)>> = HashMap::new();
+    for way in &ways {
+        let mut start = way.nodes[0];
+        let mut length = 0.0;
+        for step in way.nodes.windows(2) {
+            let (from, to) = (&points[&step[0]], &points[&step[1]]);
+            length += haversine((from.latitude, from.longitude), (to.latitude, to.longitude));
+            if names.contains_key(&step[1]) {
+                if start != step[1] {
+                    for (a, b) in [(start, step[1]), (step[1], start)] {
+                        let mut attributes = way.attributes.clone();
+                        attributes.oneway &= (a == start) != way.reversed;
+                        let entry = neighbours.entry(a).or_default().entry(b).or_insert((length, attributes.clone()));
+                        if length < entry.0 {
+                            *entry = (length, attributes);
+                        }
+                    }
+                } else {
+                    debug!("skipping loop at node {} ...", start);
+                }
+                start = step[1];
+                length = 0.0;
+            }
+        }
+    }
+
+    let mut intersections : Vec<Intersection> = Vec::new();
+    for id in junctions {
+        let links = match neighbours.get(&id) {
+            Some(links) => links,
+            None => continue
+        };
+        let (name, address) = &names[&id];
+        let point = &points[&id];
+        intersections.push(Intersection {
+            name: name.clone(),
+            address: address.clone(),
+            id: intersections.len(),
+            altitude: point.altitude,
+            latitude: point.latitude,
+            longitude: point.longitude,
+            neighbours: links
+                .iter()
+                .map(|(to, (length, attributes))| {
+                    let link = if attributes.is_empty() {
+                        Link::Length(format!("{:.0}", length.ceil()))
+                    } else {
+                        Link::Street { length: length.ceil(), attributes: attributes.clone() }
+                    };
+                    (names[to].0.clone(), link)
+                })
+                .collect()
+        });
+    }
+    info!("imported {} intersections.", intersections.len());
+
+    Ok(intersections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXTRACT : &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6">
+  <node id="1" lat="47.5600" lon="-122.3800"><tag k="ele" v="30"/></node>
+  <node id="2" lat="47.5610" lon="-122.3800"/>
+  <node id="3" lat="47.5620" lon="-122.3800"/>
+  <node id="4" lat="47.5610" lon="-122.3790"/>
+  <node id="5" lat="47.5600" lon="-122.3790"/>
+  <node id="6" lat="47.5620" lon="-122.3790"/>
+  <way id="10">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="residential"/><tag k="name" v="Main St"/><tag k="surface" v="asphalt"/>
+  </way>
+  <way id="11">
+    <nd ref="4"/><nd ref="2"/>
+    <tag k="highway" v="cycleway"/><tag k="name" v="Oak Ave"/><tag k="oneway" v="-1"/>
+  </way>
+  <way id="12">
+    <nd ref="5"/><nd ref="4"/>
+    <tag k="highway" v="motorway"/>
+  </way>
+  <way id="13">
+    <nd ref="4"/><nd ref="6"/>
+    <tag k="highway" v="footway"/>
+  </way>
+</osm>"#;
+
+    #[test]
+    fn cyclable_ways() {
+        let tags = |pairs : &[(&'static str, &'static str)]| pairs.iter().copied().collect::<HashMap<&str, &str>>();
+        assert!(cyclable(&tags(&[("highway", "residential")])));
+        assert!(!cyclable(&tags(&[("highway", "motorway")])));
+        assert!(!cyclable(&tags(&[("highway", "footway")])));
+        assert!(cyclable(&tags(&[("highway", "footway"), ("bicycle", "designated")])));
+        assert!(!cyclable(&tags(&[("highway", "service"), ("access", "private")])));
+        assert!(!cyclable(&tags(&[("highway", "residential"), ("bicycle", "no")])));
+    }
+
+    #[test]
+    fn splits_ways_at_junctions() {
+        let intersections = read(EXTRACT).unwrap();
+        let mut names : Vec<&str> = intersections.iter().map(|intersection| intersection.name.as_str()).collect();
+        names.sort();
+        // Both ends of Main St are on the same streets, so their OSM ids tell them apart.
+        assert_eq!(names, vec!["main-st&oak-ave", "main-st-1", "main-st-3", "oak-ave"]);
+
+        let find = |name : &str| intersections.iter().find(|intersection| intersection.name == name).unwrap();
+        let junction = find("main-st&oak-ave");
+        assert_eq!(junction.address, "Main St & Oak Ave");
+        let mut neighbours : Vec<&String> = junction.neighbours.keys().collect();
+        neighbours.sort();
+        assert_eq!(neighbours, vec!["main-st-1", "main-st-3", "oak-ave"]);
+        assert_eq!(find("main-st-1").altitude, 30.0);
+
+        // Oak Ave may only be ridden from the junction, against the order of its nodes.
+        let oak = junction.neighbours["oak-ave"].attributes();
+        assert!(oak.oneway);
+        assert_eq!(oak.class.as_deref(), Some("cycleway"));
+        assert!(!find("oak-ave").neighbours["main-st&oak-ave"].attributes().oneway);
+
+        // About 365 feet between the junction and either end of Main St.
+        let length = junction.neighbours["main-st-1"].checked_length().unwrap();
+        assert!((length - 365.0).abs() < 5.0, "{}", length);
+        assert_eq!(junction.neighbours["main-st-1"].attributes().surface.as_deref(), Some("asphalt"));
+    }
+
+    #[test]
+    fn invalid_xml() {
+        assert!(read("<osm><node").is_err());
+    }
+}
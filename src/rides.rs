@@ -0,0 +1,295 @@
+/**
+ * Splits a full coverage route into several rides that each start and finish at a home
+ * intersection (route first, cluster second). The route is cut between the streets it rides
+ * for the first time; each piece is reached from home and left for home along shortest paths.
+ * Cut points are chosen by dynamic programming: either the fewest rides under a maximum
+ * distance, or at most K rides with the longest one as short as possible (binary search on the
+ * maximum distance).
+ */
+use log::info;
+
+use std::collections::HashSet;
+
+use crate::error::{Error, Result};
+use crate::euler::Circuit;
+use crate::turns::Turns;
+use crate::{Map, INFINITE, index_map, ridden, street};
+
+#[derive(Clone, Copy)]
+pub(crate) enum Limit {
+    // At most this many rides.
+    Rides(usize),
+    // Every ride at most this long (a single street longer than this still gets its own ride).
+    Distance(i32)
+}
+
+// The route as rows of the all pairs matrix, rotated to start at 'home' if it passes through it.
+fn rotate(route : & [usize], home : usize) -> Vec<usize> {
+    let closed = route.len() > 1 && route.first() == route.last();
+    match route.iter().position(|&i| i == home) {
+        Some(pos) if closed => route[pos..].iter().chain(route[1..=pos].iter()).copied().collect(),
+        _ => route.to_vec()
+    }
+}
+
+struct Tour {
+    nodes : Vec<usize>,
+    // Length of each step, and whether it is the first time that street is ridden.
+    lengths : Vec<i32>,
+    service : Vec<bool>,
+    // Running totals over the steps, so any piece is measured in constant time.
+    distance : Vec<i32>,
+    served : Vec<usize>,
+    // Distance ridden from home to each node, and from each node back home.
+    there : Vec<i64>,
+    back : Vec<i64>
+}
+
+impl Tour {
+    fn new(map : & Map, nodes : Vec<usize>, home : usize, turns : & Turns) -> Tour {
+        let mut seen : HashSet<(usize, usize)> = HashSet::new();
+        let mut lengths = Vec::new();
+        let mut service = Vec::new();
+        let (mut distance, mut served) = (vec![0], vec![0]);
+        for step in nodes.windows(2) {
+            let (i, j) = (step[0], step[1]);
+            let length = street(map, i, j).unwrap_or(0);
+            let first = seen.insert((i.min(j), i.max(j)));
+            lengths.push(length);
+            service.push(first);
+            distance.push(distance.last().unwrap() + length);
+            served.push(served.last().unwrap() + first as usize);
+        }
+        let reach = |path : Vec<usize>| if path.is_empty() { INFINITE } else { ridden(map, &path) as i64 };
+        let there = (0..=map.nodes.len()).map(|i| if i == 0 { INFINITE } else { reach(turns.path(map, home, i)) }).collect();
+        let back = (0..=map.nodes.len()).map(|i| if i == 0 { INFINITE } else { reach(turns.path(map, i, home)) }).collect();
+        Tour { nodes, lengths, service, distance, served, there, back }
+    }
+
+    // Distance of a ride from home through steps 'from..to' and back home.
+    fn ride(&self, from : usize, to : usize) -> i64 {
+        let (start, end) = (self.nodes[from], self.nodes[to]);
+        if self.there[start] >= INFINITE || self.back[end] >= INFINITE {
+            return INFINITE;
+        }
+        self.there[start] + (self.distance[to] - self.distance[from]) as i64 + self.back[end]
+    }
+}
+
+// Fewest rides (then shortest in total) with every ride within 'limit'. 'cuts' are the step
+// positions a ride may start or finish at. Returns the (start, end) steps of each ride.
+fn plan(tour : & Tour, cuts : & [usize], limit : i64) -> Option<Vec<(usize, usize)>> {
+    // 'starts[c]' is the first street ridden for the first time after cut 'c'.
+    let starts : Vec<usize> = cuts
+        .iter()
+        .map(|&cut| (cut..tour.service.len()).find(|&k| tour.service[k]).unwrap_or(cut))
+        .collect();
+    let mut best : Vec<Option<(usize, i64, usize)>> = vec![None; cuts.len()];
+    best[0] = Some((0, 0, 0));
+    for a in 0..cuts.len() {
+        let (count, total, _) = match best[a] {
+            Some(best) => best,
+            None => continue
+        };
+        for b in (a + 1)..cuts.len() {
+            let length = tour.ride(starts[a], cuts[b]);
+            let single = tour.served[cuts[b]] - tour.served[starts[a]] == 1;
+            if length >= INFINITE || (length > limit && !single) {
+                if ((tour.distance[cuts[b]] - tour.distance[starts[a]]) as i64) > limit {
+                    break;
+                }
+                continue;
+            }
+            let candidate = (count + 1, total + length, a);
+            match best[b] {
+                Some((c, t, _)) if (c, t) <= (candidate.0, candidate.1) => (),
+                _ => best[b] = Some(candidate)
+            }
+        }
+    }
+
+    let mut rides = Vec::new();
+    let mut b = cuts.len() - 1;
+    while b > 0 {
+        let (_, _, a) = best[b]?;
+        rides.push((starts[a], cuts[b]));
+        b = a;
+    }
+    rides.reverse();
+    Some(rides)
+}
+
+fn circuit(map : & Map, tour : & Tour, home : usize, from : usize, to : usize, turns : & Turns) -> Circuit {
+    let name = |i : usize| map.nodes[i - 1].1.name.clone();
+    let there = turns.path(map, home, tour.nodes[from]);
+    let back = turns.path(map, tour.nodes[to], home);
+
+    let mut route : Vec<usize> = there.clone();
+    route.extend(&tour.nodes[(from + 1)..=to]);
+    route.extend(back.iter().skip(1));
+
+    let mut deadhead = (tour.there[tour.nodes[from]] + tour.back[tour.nodes[to]]) as i32;
+    let mut repeated : Vec<Vec<String>> = Vec::new();
+    if there.len() > 1 {
+        repeated.push(there.into_iter().map(name).collect());
+    }
+    // Runs of streets already ridden, earlier in this ride or in an earlier one.
+    let mut run : Vec<usize> = Vec::new();
+    for k in from..to {
+        if tour.service[k] {
+            if !run.is_empty() {
+                repeated.push(run.drain(..).map(name).collect());
+            }
+            continue;
+        }
+        if run.is_empty() {
+            run.push(tour.nodes[k]);
+        }
+        run.push(tour.nodes[k + 1]);
+        deadhead += tour.lengths[k];
+    }
+    if !run.is_empty() {
+        repeated.push(run.into_iter().map(name).collect());
+    }
+    if back.len() > 1 {
+        repeated.push(back.into_iter().map(name).collect());
+    }
+
+    Circuit {
+        route: route.into_iter().map(name).collect(),
+        distance: tour.ride(from, to) as i32,
+        deadhead,
+        repeated
+    }
+}
+
+pub(crate) fn split(map : & Map, full : & Circuit, home : &str, limit : Limit, turns : & Turns) -> Result<Vec<Circuit>> {
+    let ids = index_map(&map.nodes);
+    let home = *ids.get(home).ok_or_else(|| Error::UnknownIntersection(home.to_string()))?;
+    let route : Vec<usize> = full.route.iter().map(|name| ids[name]).collect();
+    let tour = Tour::new(map, rotate(&route, home), home, turns);
+
+    // Rides start before, and finish after, a street ridden for the first time.
+    let mut cuts : Vec<usize> = vec![0];
+    cuts.extend((0..tour.service.len()).filter(|&k| tour.service[k]).map(|k| k + 1));
+    if cuts.len() == 1 {
+        return Ok(Vec::new());
+    }
+    if tour.ride(0, 0) >= INFINITE {
+        return Err(Error::Disconnected {
+            from: map.nodes[home - 1].1.name.clone(),
+            to: map.nodes[tour.nodes[0] - 1].1.name.clone()
+        });
+    }
+
+    let plan = match limit {
+        Limit::Distance(distance) => plan(&tour, &cuts, distance as i64),
+        Limit::Rides(rides) => {
+            // Smallest maximum distance that still fits into 'rides' rides.
+            let (mut low, mut high) = (0, tour.ride(0, *cuts.last().unwrap()));
+            while low < high {
+                let middle = low + (high - low) / 2;
+                match plan(&tour, &cuts, middle) {
+                    Some(plan) if plan.len() <= rides.max(1) => high = middle,
+                    _ => low = middle + 1
+                }
+            }
+            info!("longest of {} rides is at most {}.", rides, high);
+            plan(&tour, &cuts, high)
+        }
+    };
+
+    // Only a street that cannot be ridden to from home, or back, leaves no way to split.
+    let plan = plan.ok_or_else(|| {
+        let name = |i : usize| map.nodes[i - 1].1.name.clone();
+        match tour.nodes.iter().find(|&&i| tour.there[i] >= INFINITE) {
+            Some(&i) => Error::Disconnected { from: name(home), to: name(i) },
+            None => {
+                let i = tour.nodes.iter().copied().find(|&i| tour.back[i] >= INFINITE).unwrap_or(home);
+                Error::Disconnected { from: name(i), to: name(home) }
+            }
+        }
+    })?;
+    let rides : Vec<Circuit> = plan
+        .into_iter()
+        .map(|(from, to)| circuit(map, &tour, home, from, to, turns))
+        .collect();
+    for (pos, ride) in rides.iter().enumerate() {
+        info!("ride {}: distance = {}, deadhead = {}", pos + 1, ride.distance, ride.deadhead);
+    }
+    Ok(rides)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cli::Matching;
+    use crate::turns::Turns;
+    use crate::testing::{both, directed, map, oneway};
+
+    // Two loops of three 100 foot streets through 'h'.
+    fn loops() -> (Map, Circuit) {
+        let intersections = [("h", 0.0, 0.0), ("a", 0.001, 0.001), ("b", 0.001, -0.001), ("c", -0.001, 0.001), ("d", -0.001, -0.001)];
+        let map = map(&intersections, &both(&[("h", "a", 100), ("a", "b", 100), ("b", "h", 100), ("h", "c", 100), ("c", "d", 100), ("d", "h", 100)]));
+        let circuit = crate::undirected(&map, Matching::Blossom, None, &Turns::default()).unwrap();
+        (map, circuit)
+    }
+
+    // Every ride goes from home and back, and between them they ride every street.
+    fn check(map : & Map, rides : & [Circuit]) {
+        let mut streets : HashSet<(String, String)> = HashSet::new();
+        for ride in rides {
+            assert_eq!((ride.route.first().unwrap().as_str(), ride.route.last().unwrap().as_str()), ("h", "h"));
+            let ids = index_map(&map.nodes);
+            let path : Vec<usize> = ride.route.iter().map(|name| ids[name]).collect();
+            assert_eq!(ridden(map, &path), ride.distance);
+            for step in ride.route.windows(2) {
+                streets.insert((step[0].clone().min(step[1].clone()), step[0].clone().max(step[1].clone())));
+            }
+        }
+        assert_eq!(streets.len(), 6);
+    }
+
+    #[test]
+    fn splits_at_home_under_a_distance() {
+        let (map, circuit) = loops();
+        let rides = split(&map, &circuit, "h", Limit::Distance(300), &Turns::default()).unwrap();
+        check(&map, &rides);
+        assert_eq!(rides.iter().map(|ride| (ride.distance, ride.deadhead)).collect::<Vec<_>>(), vec![(300, 0), (300, 0)]);
+
+        // No ride there and back fits under 150, so every street gets a ride of its own.
+        let rides = split(&map, &circuit, "h", Limit::Distance(150), &Turns::default()).unwrap();
+        check(&map, &rides);
+        assert_eq!(rides.iter().map(|ride| ride.distance).collect::<Vec<_>>(), vec![200, 300, 200, 200, 300, 200]);
+    }
+
+    #[test]
+    fn splits_into_a_number_of_rides() {
+        let (map, circuit) = loops();
+        let rides = split(&map, &circuit, "h", Limit::Rides(1), &Turns::default()).unwrap();
+        check(&map, &rides);
+        assert_eq!(rides.len(), 1);
+        assert_eq!(rides[0].distance, 600);
+
+        // Two rides, one loop each, is as short as the longest can be.
+        let rides = split(&map, &circuit, "h", Limit::Rides(2), &Turns::default()).unwrap();
+        check(&map, &rides);
+        assert_eq!(rides.iter().map(|ride| ride.distance).collect::<Vec<_>>(), vec![300, 300]);
+
+        assert!(matches!(split(&map, &circuit, "x", Limit::Rides(2), &Turns::default()), Err(Error::UnknownIntersection(_))));
+    }
+
+    #[test]
+    fn refuses_streets_with_no_way_home() {
+        // Nothing leads back to 'h' once the one-way street to 'a' is ridden.
+        let intersections = [("h", 0.0, 0.0), ("a", 0.001, 0.0), ("b", 0.002, 0.0)];
+        let mut links = vec![("h", "a", oneway(100))];
+        links.extend(both(&[("a", "b", 100)]));
+        let map = directed(&intersections, &links);
+        let route = ["h", "a", "b", "a"].iter().map(|name| name.to_string()).collect();
+        let circuit = Circuit { route, distance: 300, deadhead: 100, repeated: Vec::new() };
+        let rides = split(&map, &circuit, "h", Limit::Distance(1000), &Turns::default());
+        assert!(matches!(rides, Err(Error::Disconnected { from, to }) if from == "a" && to == "h"));
+    }
+}
@@ -0,0 +1,142 @@
+/**
+ * Rural postman: rides a required subset of the streets (e.g. only the greenways, or the streets
+ * not yet ridden) while travelling on any street in between. Following Frederickson's heuristic,
+ * the required streets are split into connected components, the components are joined along a
+ * minimum spanning tree of shortest paths, the odd nodes are paired with a blossom matching, and
+ * the resulting even multigraph is walked with Hierholzer's algorithm.
+ */
+use log::{debug,info,warn};
+
+use std::collections::HashSet;
+
+use crate::blossom;
+use crate::components;
+use crate::error::{Error, Result};
+use crate::euler::{Circuit, Multigraph, hierholzer, pairing, repeat};
+use crate::turns::Turns;
+use crate::{Map, index_map, street};
+
+// Reads the required streets, one 'from to' pair per line. Anything after the two names is
+// ignored, so the text output of 'export' can be filtered and passed straight back in.
+pub(crate) fn read(contents : &str) -> Vec<(String, String)> {
+    contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut names = line.split_whitespace();
+            match (names.next(), names.next()) {
+                (Some(from), Some(to)) => Some((from.to_string(), to.to_string())),
+                _ => {
+                    warn!("skipping required street '{}', expected 'from to' ...", line);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn circuit(map : & Map, required : & [(String, String)], turns : & Turns) -> Result<Circuit> {
+    let ids = index_map(&map.nodes);
+    let size = map.nodes.len() + 1;
+    let name = |i : usize| map.nodes[i - 1].1.name.clone();
+
+    let mut graph = Multigraph::new(size);
+    let mut seen : HashSet<(usize, usize)> = HashSet::new();
+    for (from, to) in required {
+        if !ids.contains_key(from) || !ids.contains_key(to) {
+            warn!("skipping required street {} {}, unknown intersection ...", from, to);
+            continue;
+        }
+        let (i, j) = (ids[from], ids[to]);
+        if !seen.insert((i.min(j), i.max(j))) {
+            debug!("skipping duplicate required street {} {} ...", from, to);
+            continue;
+        }
+        match street(map, i, j) {
+            Some(length) => graph.add(i, j, length),
+            None => warn!("skipping required street {} {}, no such street ...", from, to)
+        }
+    }
+    let distance : i32 = graph.edges.iter().map(|(_, _, length)| length).sum();
+    let count = graph.edges.len();
+
+    let mut deadhead = 0;
+    let mut repeated : Vec<Vec<String>> = Vec::new();
+    let mut ride = |graph : &mut Multigraph, from : usize, to : usize| {
+        let path = turns.path(map, from, to);
+        if path.is_empty() {
+            return Err(Error::Disconnected { from: name(from), to: name(to) });
+        }
+        deadhead += repeat(map, graph, path, &mut repeated);
+        Ok(())
+    };
+
+    // Join the required components.
+    let components = components::pieces(size, &graph.edges);
+    info!("found {} required streets in {} components.", count, components.len());
+    for (from, to) in components::connect(&components, |i, j| map.array[i][j]) {
+        ride(&mut graph, from, to)?;
+    }
+
+    // Pair up the odd nodes.
+    let odd : Vec<usize> = (1..size).filter(|&i| graph.adjacent[i].len() % 2 == 1).collect();
+    info!("found {} odd nodes.", odd.len());
+    if !odd.is_empty() {
+        let (matrix, keys) = pairing(map, &odd, |a, b| map.array[odd[a]][odd[b]]);
+        let pairs = blossom::solve(&matrix, &keys);
+        // Odd nodes left out of the matching cannot reach any other.
+        let paired : Vec<usize> = pairs.iter().flat_map(|&(a, b)| [a, b]).collect();
+        let left : Vec<usize> = (1..=odd.len()).filter(|a| !paired.contains(a)).collect();
+        if left.len() > 1 {
+            return Err(Error::Disconnected { from: name(odd[left[0] - 1]), to: name(odd[left[1] - 1]) });
+        }
+        for (a, b) in pairs {
+            ride(&mut graph, odd[a - 1], odd[b - 1])?;
+        }
+    }
+    info!("added {} streets for a deadhead of {}.", graph.edges.len() - count, deadhead);
+
+    let route = match graph.edges.first() {
+        Some(&(start, _, _)) => hierholzer(map, &graph, start, turns)?,
+        None => Vec::new()
+    };
+
+    Ok(Circuit {
+        route: route.into_iter().map(name).collect(),
+        distance: distance + deadhead,
+        deadhead,
+        repeated
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::{both, map};
+
+    #[test]
+    fn reads_required_streets() {
+        let required = read("# greenways\na b 264\n\nc\n d  e\n");
+        assert_eq!(required, vec![("a".to_string(), "b".to_string()), ("d".to_string(), "e".to_string())]);
+    }
+
+    #[test]
+    fn covers_only_required_streets() {
+        // A square with a diagonal; only two opposite sides have to be ridden.
+        let intersections = [("a", 0.0, 0.0), ("b", 0.0, 0.001), ("c", 0.001, 0.001), ("d", 0.001, 0.0)];
+        let map = map(&intersections, &both(&[("a", "b", 100), ("b", "c", 100), ("c", "d", 100), ("d", "a", 100), ("a", "c", 140)]));
+        let required = read("a b\nc d\n");
+        let circuit = circuit(&map, &required, &Turns::default()).unwrap();
+
+        assert_eq!(circuit.route.first(), circuit.route.last());
+        let steps : Vec<(&str, &str)> = circuit.route.windows(2).map(|step| (step[0].as_str(), step[1].as_str())).collect();
+        for (from, to) in &required {
+            assert!(steps.iter().any(|&step| step == (from, to) || step == (to, from)), "{:?}", steps);
+        }
+        // Joined along the other two sides, never the diagonal.
+        assert_eq!(steps.len(), 4);
+        assert_eq!((circuit.distance, circuit.deadhead), (400, 200));
+    }
+}
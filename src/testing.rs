@@ -0,0 +1,71 @@
+/**
+ * Small maps built in memory, and pseudo-random numbers, for the tests of the solvers.
+ */
+use std::collections::HashMap;
+
+use crate::attributes::{Attributes, Link};
+use crate::cli::{Lengths, Source};
+use crate::{Intersection, Map, Node, assemble, build_map, geo};
+
+// A link 'feet' long, in the original format.
+pub(crate) fn length(feet : i32) -> Link {
+    Link::Length(feet.to_string())
+}
+
+// A link 'feet' long that may only be ridden towards the neighbour.
+pub(crate) fn oneway(feet : i32) -> Link {
+    Link::Street { length: feet as f64, attributes: Attributes { oneway: true, ..Attributes::default() } }
+}
+
+// Nodes for intersections '(name, latitude, longitude)', numbered in order, and the links each
+// one lists: '(from, to, link)'. A street both ends list is given twice.
+pub(crate) fn nodes(intersections : & [(&str, f64, f64)], links : & [(&str, &str, Link)]) -> Vec<(usize, Node)> {
+    let intersections : Vec<Intersection> = intersections
+        .iter()
+        .enumerate()
+        .map(|(pos, &(name, latitude, longitude))| Intersection {
+            name: name.to_string(),
+            address: String::new(),
+            id: pos + 1,
+            altitude: 0.0,
+            latitude,
+            longitude,
+            neighbours: links
+                .iter()
+                .filter(|(from, _, _)| *from == name)
+                .map(|(_, to, link)| (to.to_string(), link.clone()))
+                .collect::<HashMap<String, Link>>()
+        })
+        .collect();
+    let lengths = Lengths { source: Source::Map, tolerance: 0.5, three_d: false };
+    build_map(&intersections, &geo::Climbing::default(), &[], &lengths).unwrap()
+}
+
+// The map as the undirected mode sees it, with paths along every street both ways.
+pub(crate) fn map(intersections : & [(&str, f64, f64)], links : & [(&str, &str, Link)]) -> Map {
+    assemble(nodes(intersections, links), &[], &[], false)
+}
+
+// The map as the directed and mixed modes see it, with paths only along links as they are listed.
+pub(crate) fn directed(intersections : & [(&str, f64, f64)], links : & [(&str, &str, Link)]) -> Map {
+    assemble(nodes(intersections, links), &[], &[], true)
+}
+
+// Streets '(from, to, feet)' listed by both ends, as two-way streets are.
+pub(crate) fn both<'a>(streets : & [(&'a str, &'a str, i32)]) -> Vec<(&'a str, &'a str, Link)> {
+    streets
+        .iter()
+        .flat_map(|&(from, to, feet)| [(from, to, length(feet)), (to, from, length(feet))])
+        .collect()
+}
+
+// Pseudo-random numbers from a linear congruential generator, the same on every run.
+pub(crate) struct Random(pub(crate) u64);
+
+impl Random {
+    // A number below 'bound'.
+    pub(crate) fn next(&mut self, bound : u64) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 33) % bound
+    }
+}
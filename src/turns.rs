@@ -0,0 +1,150 @@
+/**
+ * Turn penalties at intersections. The turn from 'a -> b' onto 'b -> c' is read from the compass
+ * bearings of the two streets: within 30 degrees of straight on is free, beyond 150 degrees is a
+ * U-turn, anything else is a left or right turn (left crosses the oncoming traffic).
+ */
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::geo::bearing;
+use crate::{Map, INFINITE, index_map, path, wrong_way};
+
+const STRAIGHT : f64 = 30.0;
+const UTURN : f64 = 150.0;
+
+// Extra cost of each kind of turn, in the same units as street lengths.
+#[derive(Clone, Copy, Default)]
+pub struct Turns {
+    pub left : i32,
+    pub right : i32,
+    pub uturn : i32
+}
+
+impl Turns {
+    pub fn enabled(&self) -> bool {
+        self.left != 0 || self.right != 0 || self.uturn != 0
+    }
+
+    // Penalty for riding through rows 'a -> b -> c'; 'a' is 0 when there is no previous street.
+    pub(crate) fn penalty(&self, map : & Map, a : usize, b : usize, c : usize) -> i32 {
+        if a == 0 || !self.enabled() {
+            return 0;
+        }
+        if a == c {
+            return self.uturn;
+        }
+        let point = |i : usize| (map.nodes[i - 1].1.latitude, map.nodes[i - 1].1.longitude);
+        // Positive angles turn clockwise, i.e. to the right.
+        let angle = (bearing(point(b), point(c)) - bearing(point(a), point(b)) + 540.0) % 360.0 - 180.0;
+        if angle.abs() <= STRAIGHT {
+            0
+        } else if angle.abs() >= UTURN {
+            self.uturn
+        } else if angle > 0.0 {
+            self.right
+        } else {
+            self.left
+        }
+    }
+
+    // Cheapest path between two rows of the all pairs matrix counting turns as well as street
+    // costs (Dijkstra over the streets rather than the intersections, so each turn is known).
+    // Falls back to the all pairs path when turns are free.
+    pub(crate) fn path(&self, map : & Map, from : usize, to : usize) -> Vec<usize> {
+        if !self.enabled() || from == to {
+            return path(&map.next, from, to);
+        }
+        let ids = index_map(&map.nodes);
+        let mut streets : Vec<(usize, usize, i32)> = Vec::new();
+        let mut outgoing : Vec<Vec<usize>> = vec![Vec::new(); map.nodes.len() + 1];
+        for (_, node) in &map.nodes {
+            for edge in node.edges.values().filter(|edge| !(map.oneway && wrong_way(&map.nodes, &ids, edge))) {
+                outgoing[ids[&edge.from]].push(streets.len());
+                streets.push((ids[&edge.from], ids[&edge.to], edge.cost));
+            }
+        }
+        for (&(i, j), &length) in &map.connectors {
+            for (a, b) in [(i, j), (j, i)] {
+                outgoing[a].push(streets.len());
+                streets.push((a, b, length));
+            }
+        }
+
+        // Summed in 'i64', so large penalties cannot overflow.
+        let mut distance : Vec<i64> = vec![INFINITE; streets.len()];
+        let mut previous : Vec<usize> = vec![usize::MAX; streets.len()];
+        let mut heap = BinaryHeap::new();
+        for &id in &outgoing[from] {
+            distance[id] = streets[id].2 as i64;
+            heap.push(Reverse((distance[id], id)));
+        }
+        let mut last = usize::MAX;
+        while let Some(Reverse((d, id))) = heap.pop() {
+            if d > distance[id] {
+                continue;
+            }
+            let (a, b, _) = streets[id];
+            if b == to {
+                last = id;
+                break;
+            }
+            for &next in &outgoing[b] {
+                let (_, c, cost) = streets[next];
+                let total = d + cost as i64 + self.penalty(map, a, b, c) as i64;
+                if total < distance[next] {
+                    distance[next] = total;
+                    previous[next] = id;
+                    heap.push(Reverse((total, next)));
+                }
+            }
+        }
+        if last == usize::MAX {
+            return Vec::new();
+        }
+
+        let mut path = vec![to];
+        let mut id = last;
+        while id != usize::MAX {
+            path.push(streets[id].0);
+            id = previous[id];
+        }
+        path.reverse();
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::{both, map};
+
+    // Riding north from 'p' to 's', then on to 't' either east then north ('e') or north then
+    // east ('n'); the way through 'e' is shorter but turns left.
+    fn grid() -> Map {
+        let intersections = [("p", 0.0, 0.0), ("s", 0.001, 0.0), ("n", 0.002, 0.0), ("e", 0.001, 0.001), ("t", 0.002, 0.001)];
+        map(&intersections, &both(&[("p", "s", 100), ("s", "e", 100), ("e", "t", 100), ("s", "n", 100), ("n", "t", 110)]))
+    }
+
+    #[test]
+    fn reads_turns_from_bearings() {
+        let map = grid();
+        let turns = Turns { left: 1, right: 2, uturn: 3 };
+        // p, s, n, e, t are rows 1 to 5.
+        assert_eq!(turns.penalty(&map, 1, 2, 3), 0);
+        assert_eq!(turns.penalty(&map, 1, 2, 4), 2);
+        assert_eq!(turns.penalty(&map, 2, 4, 5), 1);
+        assert_eq!(turns.penalty(&map, 2, 4, 2), 3);
+        assert_eq!(turns.penalty(&map, 0, 2, 4), 0);
+    }
+
+    #[test]
+    fn penalties_change_the_path() {
+        let map = grid();
+        assert_eq!(Turns::default().path(&map, 1, 5), vec![1, 2, 4, 5]);
+        assert_eq!(Turns { left: 50, right: 0, uturn: 0 }.path(&map, 1, 5), vec![1, 2, 3, 5]);
+        assert_eq!(Turns { left: 0, right: 50, uturn: 0 }.path(&map, 1, 5), vec![1, 2, 4, 5]);
+        // However large, a penalty is only ever a cost to avoid.
+        assert_eq!(Turns { left: i32::MAX, right: i32::MAX, uturn: i32::MAX }.path(&map, 1, 5), vec![1, 2, 3, 5]);
+    }
+}
@@ -0,0 +1,328 @@
+/**
+ * Data-quality checks for a map, run before anything is built from it so that bad data is
+ * reported with the line it is on rather than panicking half way through a solve.
+ */
+use serde::Serialize;
+use serde_json::Value;
+
+use std::collections::HashMap;
+
+use crate::geo::{disagrees, distance, haversine};
+use crate::{Intersection, osm};
+
+// Lengths of the same street in both directions may differ by this much (feet) before it is
+// reported, since both are rounded up independently.
+const LENGTH_TOLERANCE : f64 = 1.0;
+// An intersection this many times further from the middle of the map than the median
+// intersection (and at least a mile away) is reported as misplaced.
+const OUTLIER_FACTOR : f64 = 10.0;
+const MILE : f64 = 5280.0;
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+    Error,
+    Warning
+}
+
+#[derive(Serialize)]
+pub(crate) struct Issue {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line : Option<usize>,
+    pub severity : Severity,
+    pub message : String
+}
+
+// Finds the lines of each intersection object in the JSON text, so issues can point at them.
+struct Lines<'a> {
+    lines : Vec<&'a str>,
+    // First and last line (1-based) of each top level object.
+    objects : Vec<(usize, usize)>
+}
+
+impl<'a> Lines<'a> {
+    fn new(contents : &'a str) -> Lines<'a> {
+        let mut objects = Vec::new();
+        let (mut line, mut depth, mut start) = (1, 0, 0);
+        let (mut string, mut escape) = (false, false);
+        for c in contents.chars() {
+            if string {
+                match c {
+                    _ if escape => escape = false,
+                    '\\' => escape = true,
+                    '"' => string = false,
+                    _ => ()
+                }
+            } else {
+                match c {
+                    '"' => string = true,
+                    '{' | '[' => {
+                        depth += 1;
+                        if depth == 2 && c == '{' {
+                            start = line;
+                        }
+                    },
+                    '}' | ']' => {
+                        if depth == 2 && c == '}' {
+                            objects.push((start, line));
+                        }
+                        depth -= 1;
+                    },
+                    _ => ()
+                }
+            }
+            if c == '\n' {
+                line += 1;
+            }
+        }
+        Lines { lines: contents.lines().collect(), objects }
+    }
+
+    // Line of the object at 'index', or of the key '"key":' inside it if there is one.
+    fn find(&self, index : usize, key : Option<&str>) -> Option<usize> {
+        let &(start, end) = self.objects.get(index)?;
+        let key = match key {
+            Some(key) => serde_json::to_string(key).unwrap(),
+            None => return Some(start)
+        };
+        (start..=end)
+            .find(|&line| {
+                self.lines.get(line - 1).is_some_and(|text| {
+                    text.match_indices(&key).any(|(pos, _)| text[pos + key.len()..].trim_start().starts_with(':'))
+                })
+            })
+            .or(Some(start))
+    }
+}
+
+struct Report<'a> {
+    lines : Option<Lines<'a>>,
+    // Position in the file of each intersection that could be read.
+    positions : Vec<usize>,
+    issues : Vec<Issue>
+}
+
+impl Report<'_> {
+    fn add(&mut self, severity : Severity, index : usize, key : Option<&str>, message : String) {
+        let index = self.positions.get(index).copied().unwrap_or(index);
+        let line = self.lines.as_ref().and_then(|lines| lines.find(index, key));
+        self.issues.push(Issue { line, severity, message });
+    }
+}
+
+fn median(values : &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    values[values.len() / 2]
+}
+
+// 'geometry' is the tolerance for lengths that disagree with the coordinates (see
+// 'geo::disagrees') and whether distances follow the slope, if they are checked at all.
+fn check(report : &mut Report, intersections : & [Intersection], geometry : Option<(f64, bool)>) {
+    let mut names : HashMap<&str, usize> = HashMap::new();
+    let mut ids : HashMap<usize, usize> = HashMap::new();
+    for (index, intersection) in intersections.iter().enumerate() {
+        if let Some(first) = names.insert(&intersection.name, index) {
+            report.add(Severity::Error, index, Some("name"),
+                format!("duplicate name '{}' (also intersection {})", intersection.name, report.positions[first] + 1));
+        }
+        if let Some(first) = ids.insert(intersection.id, index) {
+            report.add(Severity::Error, index, Some("id"),
+                format!("duplicate id {} ('{}' and '{}')", intersection.id, intersections[first].name, intersection.name));
+        }
+        if !(-90.0..=90.0).contains(&intersection.latitude) || !(-180.0..=180.0).contains(&intersection.longitude) {
+            report.add(Severity::Error, index, Some("latitude"),
+                format!("'{}' has coordinates out of range ({}, {})", intersection.name, intersection.latitude, intersection.longitude));
+        }
+    }
+
+    for (index, intersection) in intersections.iter().enumerate() {
+        let mut neighbours : Vec<(&String, _)> = intersection.neighbours.iter().collect();
+        neighbours.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, link) in neighbours {
+            let at = Some(name.as_str());
+            if *name == intersection.name {
+                report.add(Severity::Error, index, at, format!("'{}' lists itself as a neighbour", name));
+                continue;
+            }
+            let length = match link.checked_length() {
+                Some(length) if crate::feet(length).is_some() => length,
+                Some(length) => {
+                    report.add(Severity::Error, index, at, format!("length {} from '{}' to '{}' is negative or too long", length, intersection.name, name));
+                    continue;
+                },
+                None => {
+                    report.add(Severity::Error, index, at, format!("length from '{}' to '{}' is not a number", intersection.name, name));
+                    continue;
+                }
+            };
+            let other = match names.get(name.as_str()) {
+                Some(&other) => &intersections[other],
+                None => {
+                    report.add(Severity::Error, index, at, format!("'{}' has an unknown neighbour '{}'", intersection.name, name));
+                    continue;
+                }
+            };
+            if let Some((tolerance, three_d)) = geometry {
+                let point = |i : &Intersection| (i.latitude, i.longitude, i.altitude);
+                let straight = distance(point(intersection), point(other), three_d);
+                if disagrees(length, straight, tolerance) {
+                    report.add(Severity::Warning, index, at,
+                        format!("'{}' to '{}' is {} long but {:.0} between the intersections", intersection.name, name, length, straight));
+                }
+            }
+            match other.neighbours.get(&intersection.name) {
+                None if !link.attributes().oneway => report.add(Severity::Warning, index, at,
+                    format!("'{}' lists '{}' but not the other way round", intersection.name, name)),
+                Some(back) => match back.checked_length() {
+                    Some(back) if (back - length).abs() > LENGTH_TOLERANCE && intersection.name < other.name => report.add(Severity::Warning, index, at,
+                        format!("'{}' to '{}' is {} long but {} the other way", intersection.name, name, length, back)),
+                    _ => ()
+                },
+                None => ()
+            }
+        }
+    }
+
+    // Intersections far from the rest of the map, usually a typo in the coordinates.
+    if intersections.len() > 2 {
+        let centre = (
+            median(&mut intersections.iter().map(|i| i.latitude).collect::<Vec<_>>()),
+            median(&mut intersections.iter().map(|i| i.longitude).collect::<Vec<_>>())
+        );
+        let distances : Vec<f64> = intersections
+            .iter()
+            .map(|i| haversine(centre, (i.latitude, i.longitude)))
+            .collect();
+        let typical = median(&mut distances.clone());
+        for (index, intersection) in intersections.iter().enumerate() {
+            if distances[index] > f64::max(OUTLIER_FACTOR * typical, MILE) {
+                report.add(Severity::Warning, index, Some("latitude"),
+                    format!("'{}' is {:.1} miles from the middle of the map", intersection.name, distances[index] / MILE));
+            }
+        }
+    }
+}
+
+// Checks the map at 'filename' (JSON, or an .osm extract without line numbers).
+pub(crate) fn validate(filename : &str, contents : &str, geometry : Option<(f64, bool)>) -> Vec<Issue> {
+    let mut report = Report { lines: None, positions: Vec::new(), issues: Vec::new() };
+    if filename.ends_with(".osm") {
+        match osm::read(contents) {
+            Ok(intersections) => check(&mut report, &intersections, geometry),
+            Err(error) => report.issues.push(Issue {
+                line: Some(error.pos().row as usize),
+                severity: Severity::Error,
+                message: error.to_string()
+            })
+        }
+        return report.issues;
+    }
+
+    let values : Vec<Value> = match serde_json::from_str(contents) {
+        Ok(values) => values,
+        Err(error) => {
+            report.issues.push(Issue { line: Some(error.line()), severity: Severity::Error, message: error.to_string() });
+            return report.issues;
+        }
+    };
+    report.lines = Some(Lines::new(contents));
+
+    let mut intersections : Vec<Intersection> = Vec::new();
+    let mut positions : Vec<usize> = Vec::new();
+    for (index, value) in values.into_iter().enumerate() {
+        match serde_json::from_value::<Intersection>(value) {
+            Ok(intersection) => {
+                intersections.push(intersection);
+                positions.push(index);
+            },
+            Err(error) => report.add(Severity::Error, index, None, format!("intersection {} is malformed: {}", index + 1, error))
+        }
+    }
+    report.positions = positions;
+    check(&mut report, &intersections, geometry);
+    report.issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP : &str = r#"[
+    {
+        "name": "a", "address": "", "id": 0,
+        "latitude": 47.56, "longitude": -122.38, "altitude": 0,
+        "neighbours": { "b": "100", "c": "ten", "z": "5" }
+    },
+    {
+        "name": "b", "address": "", "id": 1,
+        "latitude": 47.561, "longitude": -122.38, "altitude": 0,
+        "neighbours": { "a": "120" }
+    },
+    {
+        "name": "c", "address": "", "id": 1,
+        "latitude": 47.56, "longitude": -122.379, "altitude": 0,
+        "neighbours": {}
+    },
+    {
+        "name": "c", "address": "", "id": 3,
+        "latitude": 47.56, "longitude": -122.381, "altitude": 0,
+        "neighbours": {}
+    },
+    {
+        "name": "d", "id": 4
+    }
+]"#;
+
+    fn messages(issues : & [Issue]) -> Vec<(Option<usize>, String)> {
+        issues.iter().map(|issue| (issue.line, issue.message.clone())).collect()
+    }
+
+    #[test]
+    fn reports_issues_by_line() {
+        let issues = validate("map.json", MAP, None);
+        let expected = vec![
+            (Some(22), "intersection 5 is malformed: missing field `address`".to_string()),
+            (Some(13), "duplicate id 1 ('b' and 'c')".to_string()),
+            (Some(18), "duplicate name 'c' (also intersection 3)".to_string()),
+            (Some(5), "'a' to 'b' is 100 long but 120 the other way".to_string()),
+            (Some(5), "length from 'a' to 'c' is not a number".to_string()),
+            (Some(5), "'a' has an unknown neighbour 'z'".to_string())
+        ];
+        assert_eq!(messages(&issues), expected);
+        assert!(issues.iter().all(|issue| issue.severity == Severity::Error || issue.message.contains("other way")));
+    }
+
+    #[test]
+    fn checks_lengths_against_coordinates() {
+        // 'a' and 'b' are about 365 feet apart.
+        let issues = validate("map.json", MAP, Some((0.1, false)));
+        let geometry : Vec<String> = issues
+            .iter()
+            .filter(|issue| issue.message.contains("between the intersections"))
+            .map(|issue| issue.message.clone())
+            .collect();
+        assert_eq!(geometry, vec![
+            "'a' to 'b' is 100 long but 365 between the intersections".to_string(),
+            "'b' to 'a' is 120 long but 365 between the intersections".to_string()
+        ]);
+    }
+
+    #[test]
+    fn reports_invalid_json() {
+        let issues = validate("map.json", "[\n  {\n    \"name\": \n", None);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, Some(4));
+    }
+
+    #[test]
+    fn numbers_duplicates_among_every_intersection() {
+        // The malformed first intersection is left out of the checks but still counted.
+        let map = r#"[
+            { "name": "x" },
+            { "name": "c", "address": "", "id": 1, "latitude": 0, "longitude": 0, "altitude": 0, "neighbours": {} },
+            { "name": "c", "address": "", "id": 2, "latitude": 0, "longitude": 0, "altitude": 0, "neighbours": {} }
+        ]"#;
+        let issues = validate("map.json", map, None);
+        assert!(messages(&issues).contains(&(Some(4), "duplicate name 'c' (also intersection 2)".to_string())), "{:?}", messages(&issues));
+    }
+}